@@ -1,5 +1,3 @@
-
-
 use string_cache::DefaultAtom;
 
 fn main() {
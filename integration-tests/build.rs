@@ -1,10 +1,25 @@
-use string_cache_codegen;
-
 use std::env;
 use std::path::Path;
 
 fn main() {
+    // A second set, with some atoms in common with `TestAtom`.
+    string_cache_codegen::AtomType::new("other::OtherAtom", "other_atom!")
+        .atoms(&["defaults", "font-weight", "svg-only-atom", "body"])
+        .visibility("pub(crate)")
+        .attributes(&["#[derive(Debug)]"])
+        .with_all_constant(true)
+        .with_values(
+            "(&'static str, u32)",
+            "other_atom_value",
+            vec![("body", "(\"body\", 1)"), ("defaults", "(\"defaults\", 2)")],
+        )
+        .write_to_file(&Path::new(&env::var("OUT_DIR").unwrap()).join("other_atom.rs"))
+        .unwrap();
+
     string_cache_codegen::AtomType::new("TestAtom", "test_atom!")
+        .with_case_insensitive_macro("test_atom_ci!")
+        .with_debug_helpers(true)
+        .with_unknown_atom_error(true)
         .atoms(&[
             "a",
             "b",
@@ -210,3 +210,46 @@ bench_rand!(intern_rand_008, 8);
 bench_rand!(intern_rand_032, 32);
 bench_rand!(intern_rand_128, 128);
 bench_rand!(intern_rand_512, 512);
+
+/// The hasher of rustc-hash, which is commonly used for maps keyed by small values.
+#[derive(Default)]
+struct FxHasher(u64);
+
+impl std::hash::Hasher for FxHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.0 = (self.0.rotate_left(5) ^ u64::from(i)).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u32(u32::from(byte));
+        }
+    }
+}
+
+macro_rules! bench_map_lookup ( ($name:ident, $build_hasher:ty) => (
+    #[bench]
+    fn $name(b: &mut Bencher) {
+        use std::collections::HashMap;
+
+        let keys: Vec<TestAtom> = (0..1000)
+            .map(|i| mk(&format!("map key {}", i)))
+            .chain(vec![test_atom!("defaults"), test_atom!("font-weight"), mk("e")])
+            .collect();
+        let map: HashMap<TestAtom, usize, $build_hasher> =
+            keys.iter().cloned().enumerate().map(|(i, key)| (key, i)).collect();
+        b.iter(|| {
+            for key in &keys {
+                black_box(map.get(key));
+            }
+        });
+    }
+));
+
+bench_map_lookup!(map_lookup_siphash, std::collections::hash_map::RandomState);
+bench_map_lookup!(map_lookup_fxhash, std::hash::BuildHasherDefault<FxHasher>);
+bench_map_lookup!(map_lookup_atom_hasher, string_cache::BuildAtomHasher);
@@ -17,3 +17,19 @@ fn usage_with_hashmap() {
     assert_eq!(map.get(&"str_into".into()).unwrap(), &2);
     assert_eq!(map.get(&Atom::from("atom_from")).unwrap(), &3);
 }
+
+#[test]
+fn usage_with_hashmap_str_lookups() {
+    let mut map: HashMap<TestAtom, i32> = HashMap::new();
+    map.insert(test_atom!("area"), 1);
+    map.insert(Atom::from("a dynamic key"), 2);
+
+    let queries = [String::from("area"), String::from("a dynamic key")];
+    assert_eq!(map.get(&Atom::from(&*queries[0])), Some(&1));
+    assert_eq!(map.get(&Atom::from(queries[1].as_str())), Some(&2));
+    assert_eq!(map.get(&Atom::from("missing")), None);
+
+    let mut keys: Vec<&str> = map.keys().map(TestAtom::get_str).collect();
+    keys.sort_unstable();
+    assert_eq!(keys, ["a dynamic key", "area"]);
+}
@@ -21,6 +21,13 @@ use string_cache::StaticAtomSet;
 include!(concat!(env!("OUT_DIR"), "/test_atom.rs"));
 pub type Atom = TestAtom;
 
+// Not all of the `pub(crate)` generated items are used.
+#[allow(dead_code)]
+mod other {
+    include!(concat!(env!("OUT_DIR"), "/other_atom.rs"));
+}
+use other::{OtherAtom, OtherAtomStaticSet};
+
 #[test]
 fn test_as_slice() {
     let s0 = Atom::from("");
@@ -42,6 +49,34 @@ fn test_as_slice() {
     assert!(d1.as_ref() == "ZZZZZZZZZZ");
 }
 
+#[test]
+fn empty_strings_are_the_default_atom() {
+    use std::borrow::Cow;
+
+    fn check<Static: StaticAtomSet>() {
+        let default = string_cache::Atom::<Static>::default().unsafe_data();
+        assert_eq!(
+            string_cache::Atom::<Static>::from(String::new()).unsafe_data(),
+            default
+        );
+        assert_eq!(
+            string_cache::Atom::<Static>::from(Cow::Owned(String::new())).unsafe_data(),
+            default
+        );
+        assert_eq!(
+            string_cache::Atom::<Static>::from(Box::<str>::default()).unsafe_data(),
+            default
+        );
+        assert_eq!(
+            string_cache::Atom::<Static>::from("").unsafe_data(),
+            default
+        );
+    }
+    check::<TestAtomStaticSet>();
+    check::<OtherAtomStaticSet>();
+    check::<string_cache::EmptyStaticAtomSet>();
+}
+
 #[test]
 fn test_types() {
     assert!(Atom::from("").is_static());
@@ -98,6 +133,7 @@ fn default() {
 
 #[test]
 fn ord() {
+    #[allow(clippy::cmp_owned)]
     fn check(x: &str, y: &str) {
         assert_eq!(x < y, Atom::from(x) < Atom::from(y));
         assert_eq!(x.cmp(y), Atom::from(x).cmp(&Atom::from(y)));
@@ -115,6 +151,74 @@ fn ord() {
     check("z", "bbbbb");
 }
 
+#[test]
+fn ord_str() {
+    fn check(x: &str, y: &str) {
+        let atom = Atom::from(x);
+        assert_eq!(x.partial_cmp(y), atom.partial_cmp(y));
+        assert_eq!(y.partial_cmp(x), y.partial_cmp(&atom));
+        assert_eq!(x < y, atom < *y);
+        assert_eq!(y < x, *y < atom);
+    }
+
+    check("a", "body");
+    check("body", "body");
+    check("defaults", "body");
+    check("a dynamic string", "a dynamic strinG");
+    check("", "a");
+}
+
+#[test]
+fn ord_string() {
+    fn check(x: &str, y: &str) {
+        let atom = Atom::from(x);
+        let string = String::from(y);
+        assert_eq!(x.partial_cmp(y), atom.partial_cmp(&string));
+        assert_eq!(y.partial_cmp(x), string.partial_cmp(&atom));
+        assert_eq!(x == y, atom == string);
+        assert_eq!(y == x, string == atom);
+    }
+
+    check("a", "body");
+    check("body", "body");
+    check("defaults", "body");
+    check("a dynamic string", "a dynamic strinG");
+    check("", "a");
+
+    // Sorting a mixed collection, with the atoms and strings compared to each other.
+    enum Item {
+        Atom(Atom),
+        String(String),
+    }
+    fn cmp(a: &Item, b: &Item) -> std::cmp::Ordering {
+        match (a, b) {
+            (Item::Atom(a), Item::Atom(b)) => a.cmp(b),
+            (Item::String(a), Item::String(b)) => a.cmp(b),
+            (Item::Atom(a), Item::String(b)) => a.partial_cmp(b).unwrap(),
+            (Item::String(a), Item::Atom(b)) => a.partial_cmp(b).unwrap(),
+        }
+    }
+    let mut items = [
+        Item::String(String::from("font-weight")),
+        Item::Atom(Atom::from("a dynamic string")),
+        Item::String(String::from("body")),
+        Item::Atom(Atom::from("defaults")),
+        Item::Atom(Atom::from("")),
+    ];
+    items.sort_by(cmp);
+    let sorted: Vec<&str> = items
+        .iter()
+        .map(|item| match item {
+            Item::Atom(atom) => &**atom,
+            Item::String(string) => &**string,
+        })
+        .collect();
+    assert_eq!(
+        sorted,
+        ["", "a dynamic string", "body", "defaults", "font-weight"]
+    );
+}
+
 #[test]
 fn clone() {
     let s0 = Atom::from("fn");
@@ -196,6 +300,230 @@ fn test_threads() {
     }
 }
 
+#[test]
+fn test_threads_repeated_interning() {
+    let threads: Vec<_> = (0_u32..8)
+        .map(|i| {
+            thread::spawn(move || {
+                let first = Atom::from("a repeatedly interned string");
+                for _ in 0..100 {
+                    let again = Atom::from("a repeatedly interned string");
+                    assert_eq!(again.unsafe_data(), first.unsafe_data());
+                }
+                // Enough other strings to evict the first one from any per-thread cache.
+                for j in 0..64 {
+                    let other = Atom::from(format!("thread {} string {}", i, j));
+                    assert_eq!(&*other, &*format!("thread {} string {}", i, j));
+                }
+                let again = Atom::from("a repeatedly interned string");
+                assert_eq!(again, first);
+                first
+            })
+        })
+        .map(|thread| thread.join().unwrap())
+        .collect();
+    for atom in &threads {
+        assert_eq!(*atom, threads[0]);
+        assert_eq!(&**atom, "a repeatedly interned string");
+    }
+}
+
+#[test]
+fn concurrent_interning_of_a_dying_entry_gives_one_entry() {
+    use std::sync::{Arc, Barrier, Mutex};
+
+    const THREADS: usize = 8;
+    let barrier = Arc::new(Barrier::new(THREADS));
+    let pointers = Arc::new(Mutex::new(Vec::new()));
+    let threads: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let barrier = barrier.clone();
+            let pointers = pointers.clone();
+            thread::spawn(move || {
+                for _ in 0..500 {
+                    // Interned while other threads may still be dropping the previous atoms,
+                    // taking the count of the entry to zero.
+                    let atom = Atom::from("a dynamic string interned by every thread at once");
+                    pointers.lock().unwrap().push(atom.as_ptr() as usize);
+                    barrier.wait();
+                    {
+                        let pointers = pointers.lock().unwrap();
+                        assert_eq!(pointers.len(), THREADS);
+                        assert!(pointers.iter().all(|&ptr| ptr == pointers[0]));
+                    }
+                    assert_eq!(
+                        atom,
+                        Atom::from("a dynamic string interned by every thread at once")
+                    );
+                    if barrier.wait().is_leader() {
+                        pointers.lock().unwrap().clear();
+                    }
+                    barrier.wait();
+                    drop(atom);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+fn shrink_dynamic_set_while_interning() {
+    let threads: Vec<_> = (0_u32..4)
+        .map(|i| {
+            thread::spawn(move || {
+                for j in 0..200 {
+                    let atom = Atom::from(format!("shrink thread {} string {}", i, j % 10));
+                    let again = Atom::from(format!("shrink thread {} string {}", i, j % 10));
+                    assert_eq!(atom.unsafe_data(), again.unsafe_data());
+                }
+            })
+        })
+        .collect();
+    let kept = Atom::from("an atom kept across shrinking");
+    for _ in 0..20 {
+        string_cache::shrink_dynamic_set();
+    }
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(&*kept, "an atom kept across shrinking");
+    assert_eq!(kept, Atom::from("an atom kept across shrinking"));
+}
+
+#[test]
+fn clone_and_drop_a_dying_atom_across_threads() {
+    use std::sync::{Arc, Mutex};
+
+    // Every thread repeatedly takes the count of the same entry to zero while the others
+    // clone their own atoms and intern the string again, so entries die and are
+    // replaced while being looked up. Each thread publishes the entry it holds,
+    // and atoms held at the same time must all share one entry.
+    let held = Arc::new(Mutex::new(vec![None; 8]));
+    let threads: Vec<_> = (0_usize..8)
+        .map(|i| {
+            let held = held.clone();
+            thread::spawn(move || {
+                for j in 0..2000 {
+                    let atom = Atom::from("a dynamic string cloned and dropped by every thread");
+                    let clones: Vec<_> = (0..i % 4).map(|_| atom.clone()).collect();
+                    drop(atom);
+                    for clone in &clones {
+                        assert_eq!(
+                            &**clone,
+                            "a dynamic string cloned and dropped by every thread"
+                        );
+                    }
+                    if let Some(clone) = clones.first() {
+                        let ptr = clone.as_ptr() as usize;
+                        let mut held = held.lock().unwrap();
+                        for other in held.iter().flatten() {
+                            assert_eq!(*other, ptr);
+                        }
+                        held[i] = Some(ptr);
+                    }
+                    thread::yield_now();
+                    held.lock().unwrap()[i] = None;
+                    drop(clones);
+                    if i == 0 && j % 100 == 0 {
+                        string_cache::shrink_dynamic_set();
+                    }
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let atom = Atom::from("a dynamic string cloned and dropped by every thread");
+    assert_eq!(atom, atom.clone());
+}
+
+#[test]
+fn get_hash_only_depends_on_the_string() {
+    use string_cache::DefaultAtom;
+
+    for string in [
+        "",
+        "a",
+        "address",
+        "defaults",
+        "font-weight",
+        "❤💯❤💯",
+        "not static",
+        "a dynamic string longer than every static atom",
+    ] {
+        let hash = DefaultAtom::from(string).get_hash();
+        assert_eq!(Atom::from(string).get_hash(), hash, "{:?}", string);
+        assert_eq!(OtherAtom::from(string).get_hash(), hash, "{:?}", string);
+    }
+    assert!(Atom::from("font-weight").is_static());
+    assert!(DefaultAtom::from("font-weight").is_dynamic());
+}
+
+#[test]
+fn test_hash_of_index() {
+    let set = TestAtomStaticSet::get();
+    for (i, &string) in set.atoms.iter().enumerate() {
+        let atom = Atom::from_static_index(i as u32).unwrap();
+        assert_eq!(
+            set.hash_of_index(i as u32),
+            Some(atom.get_hash()),
+            "{:?}",
+            string
+        );
+    }
+    assert_eq!(set.hash_of_index(TEST_ATOM_COUNT), None);
+    assert_eq!(set.hash_of_index(u32::MAX), None);
+}
+
+#[test]
+fn static_set_debug_helpers() {
+    let atoms = TestAtomStaticSet::atoms();
+    assert_eq!(atoms.len(), TestAtomStaticSet::get().atoms.len());
+    for (index, &string) in atoms.iter().enumerate() {
+        assert_eq!(Atom::from(string).static_index(), Some(index as u32));
+    }
+    assert!(atoms.contains(&"font-weight"));
+    assert!(!atoms.contains(&"body"));
+    assert_eq!(format!("{:?}", TestAtomStaticSet), "TestAtomStaticSet");
+}
+
+#[test]
+fn dynamic_hash_is_deterministic() {
+    use string_cache::DefaultAtom;
+
+    // The hash doesn't depend on when or where the string is interned.
+    let string = "a dynamic string hashed once";
+    let hash = Atom::from(string).get_hash();
+    assert_eq!(Atom::from(string.to_owned()).get_hash(), hash);
+    assert_eq!(DefaultAtom::from(string).get_hash(), hash);
+    assert_eq!(
+        thread::spawn(move || OtherAtom::from(string).get_hash())
+            .join()
+            .unwrap(),
+        hash
+    );
+}
+
+#[test]
+fn static_sets_prefer_phf_key_0() {
+    // Key 0 gives a perfect hash for the atoms of `OtherAtom`, so lookups that miss reuse
+    // their phf hash for the dynamic entry.
+    assert_eq!(OtherAtomStaticSet::get().key, 0);
+    // It doesn't for those of `TestAtom`, which falls back to another key.
+    let set = TestAtomStaticSet::get();
+    assert_ne!(set.key, 0);
+    for &string in set.atoms {
+        assert_eq!(
+            set.get_index(string).map(|i| set.atoms[i as usize]),
+            Some(string)
+        );
+    }
+}
+
 #[test]
 fn atom_macro() {
     assert_eq!(test_atom!("a"), Atom::from("a"));
@@ -237,6 +565,36 @@ fn match_atom() {
     );
 }
 
+#[test]
+fn atom_consts() {
+    const DEFAULTS: Atom = test_atom!("defaults");
+    const BODY: Atom = ATOM_TESTATOM__62_6F_64_79;
+
+    trait Tagged {
+        const TAG: Atom = DEFAULTS;
+    }
+    struct Defaults;
+    impl Tagged for Defaults {}
+    struct Body;
+    impl Tagged for Body {
+        const TAG: Atom = BODY;
+    }
+
+    fn classify(atom: &Atom) -> u32 {
+        match *atom {
+            DEFAULTS => 1,
+            BODY => 2,
+            _ => 3,
+        }
+    }
+
+    assert_eq!(classify(&Defaults::TAG), 1);
+    assert_eq!(classify(&Body::TAG), 2);
+    assert_eq!(classify(&Atom::from("defaults")), 1);
+    assert_eq!(classify(&Atom::from("body")), 2);
+    assert_eq!(classify(&Atom::from("head")), 3);
+}
+
 #[test]
 fn ensure_deref() {
     // Ensure we can Deref to a &str
@@ -251,6 +609,230 @@ fn ensure_as_ref() {
     let _: &str = atom.as_ref();
 }
 
+#[test]
+fn atom_builder() {
+    use std::fmt::Write;
+    use string_cache::AtomBuilder;
+
+    for (a, b) in [
+        ("a", "b"),
+        ("font", "weight"),
+        ("zzzzzzzzzz", "z"),
+        ("", ""),
+    ] {
+        let mut builder = AtomBuilder::new();
+        write!(builder, "{}-{}", a, b).unwrap();
+        let atom: Atom = builder.finish();
+        let expected = Atom::from(format!("{}-{}", a, b));
+        assert_eq!(atom, expected);
+        assert_eq!(atom.unsafe_data(), expected.unsafe_data());
+    }
+    assert!(AtomBuilder::<TestAtomStaticSet>::new().finish().is_static());
+}
+
+#[test]
+fn split_whitespace_atoms() {
+    let classes = Atom::from(" a  body\tfont-weight\na-long-class-name ");
+    let parts: Vec<Atom> = classes.split_whitespace_atoms().collect();
+    assert_eq!(parts, ["a", "body", "font-weight", "a-long-class-name"]);
+    assert!(parts[1].is_inline());
+    assert!(parts[2].is_static());
+    assert_eq!(Atom::from("").split_whitespace_atoms().count(), 0);
+    assert_eq!(Atom::from(" \t ").split_whitespace_atoms().count(), 0);
+}
+
+#[test]
+fn from_char_iter() {
+    for string in [
+        "",
+        "&",
+        "❤💯",
+        "address",
+        "font-weight",
+        "a longer dynamic string",
+    ] {
+        let atom = Atom::from_char_iter(string.chars());
+        let expected = Atom::from(string.chars().collect::<String>());
+        assert_eq!(atom.unsafe_data(), expected.unsafe_data());
+    }
+}
+
+#[test]
+fn ptr_eq() {
+    for string in ["", "a", "address", "font-weight", "a dynamic string"] {
+        let atom = Atom::from(string);
+        assert!(atom.ptr_eq(&Atom::from(string.to_owned())));
+        assert!(!atom.ptr_eq(&Atom::from(format!("{}!", string))));
+    }
+}
+
+#[test]
+fn by_identity() {
+    use std::cmp::Ordering;
+    use std::collections::BTreeSet;
+    use string_cache::ByIdentity;
+
+    let strings = [
+        "",
+        "b",
+        "a",
+        "address",
+        "font-weight",
+        "a dynamic string",
+        "a",
+    ];
+    let atoms: Vec<Atom> = strings.iter().map(|&s| Atom::from(s)).collect();
+    let set: BTreeSet<ByIdentity<_>> = atoms.iter().cloned().map(ByIdentity).collect();
+    assert_eq!(set.len(), 6);
+    for atom in &atoms {
+        assert!(set.contains(&ByIdentity(atom.clone())));
+        assert_eq!(atom.cmp_by_identity(atom), Ordering::Equal);
+    }
+    assert!(!set.contains(&ByIdentity(Atom::from("c"))));
+    assert_ne!(atoms[1].cmp_by_identity(&atoms[2]), Ordering::Equal);
+    assert_eq!(
+        atoms[1].cmp_by_identity(&atoms[2]),
+        atoms[2].cmp_by_identity(&atoms[1]).reverse()
+    );
+}
+
+#[test]
+fn eq_across() {
+    for string in [
+        "",
+        "body",
+        "defaults",
+        "font-weight",
+        "svg-only-atom",
+        "a dynamic string",
+    ] {
+        let atom = Atom::from(string);
+        let other = OtherAtom::from(string);
+        assert!(atom.eq_across(&other), "{:?}", string);
+        assert!(other.eq_across(&atom), "{:?}", string);
+        assert_eq!(atom.get_hash(), other.get_hash());
+        assert!(!atom.eq_across(&OtherAtom::from(format!("{}!", string))));
+    }
+    assert!(Atom::from("font-weight").is_static() && OtherAtom::from("font-weight").is_static());
+    assert!(OtherAtom::from("❤💯❤💯").is_dynamic());
+    assert!(Atom::from("❤💯❤💯").eq_across(&OtherAtom::from("❤💯❤💯")));
+}
+
+#[test]
+fn as_ptr() {
+    // Also meant to be run under Miri, which checks that reading through the pointer
+    // stays within the bytes the atom lends out.
+    for string in ["", "body", "zzzzzzz", "defaults", "a dynamic string"] {
+        let atom = Atom::from(string);
+        let bytes = unsafe { std::slice::from_raw_parts(atom.as_ptr(), atom.len()) };
+        assert_eq!(bytes, string.as_bytes());
+    }
+    let inline = Atom::from("body");
+    assert!(inline.is_inline());
+    let address = &inline as *const Atom as usize;
+    let offset = inline.as_ptr() as usize - address;
+    assert!(offset + inline.len() <= std::mem::size_of::<Atom>());
+}
+
+#[test]
+fn to_cow() {
+    use std::borrow::Cow;
+
+    for string in ["", "defaults", "❤💯❤💯"] {
+        let atom = Atom::from(string);
+        assert_eq!(atom.as_static_str(), Some(string));
+        assert!(matches!(atom.to_cow(), Cow::Borrowed(s) if s == string));
+    }
+    for string in ["body", "a dynamic string"] {
+        let atom = Atom::from(string);
+        assert_eq!(atom.as_static_str(), None);
+        assert!(matches!(atom.to_cow(), Cow::Owned(s) if s == string));
+    }
+}
+
+#[test]
+fn as_inline_bytes() {
+    for string in ["a", "body", "abcdeé", "xyzzy01"] {
+        assert_eq!(
+            Atom::from(string).as_inline_bytes(),
+            Some(string.as_bytes())
+        );
+    }
+    for string in ["", "font-weight", "a dynamic string"] {
+        assert_eq!(Atom::from(string).as_inline_bytes(), None);
+    }
+}
+
+#[test]
+fn leak() {
+    let static_atom = test_atom!("font-weight");
+    let expected = static_atom.as_static_str().unwrap();
+    let leaked = static_atom.leak();
+    assert_eq!(leaked, "font-weight");
+    assert_eq!(leaked.as_ptr(), expected.as_ptr());
+
+    assert_eq!(Atom::from("body").leak(), "body");
+
+    let dynamic = Atom::from("a leaked dynamic string");
+    let ptr = dynamic.as_ptr();
+    let leaked = dynamic.clone().leak();
+    drop(dynamic);
+    string_cache::shrink_dynamic_set();
+    assert_eq!(leaked, "a leaked dynamic string");
+    assert_eq!(leaked.as_ptr(), ptr);
+    assert_eq!(Atom::from("a leaked dynamic string").as_ptr(), ptr);
+}
+
+#[test]
+fn as_bytes() {
+    for string in ["", "foo", "address", "zzzzzzzzzz", "❤💯"] {
+        let atom = Atom::from(string);
+        assert_eq!(atom.as_bytes(), string.as_bytes());
+    }
+}
+
+#[test]
+fn from_normalized() {
+    let lower_after_upper = |s: &mut str| {
+        s.make_ascii_uppercase();
+        s.make_ascii_lowercase();
+    };
+    for string in ["", "Foo", "Font-Weight", &"Long String ".repeat(10)] {
+        let atom = Atom::from_normalized(string, lower_after_upper);
+        assert_eq!(atom, Atom::from(string.to_ascii_lowercase()));
+    }
+    assert!(Atom::from_normalized("FONT-WEIGHT", |s| s.make_ascii_lowercase()).is_static());
+}
+
+#[test]
+fn into_ascii_case() {
+    for string in [
+        "",
+        "Body",
+        "body",
+        "BODY",
+        "Font-Weight",
+        "A Dynamic String",
+        "❤ Ä",
+    ] {
+        let atom = Atom::from(string);
+        assert_eq!(
+            atom.clone().into_ascii_lowercase(),
+            atom.to_ascii_lowercase()
+        );
+        assert_eq!(
+            atom.clone().into_ascii_uppercase(),
+            atom.to_ascii_uppercase()
+        );
+    }
+
+    let lowercase = Atom::from("an already lowercase dynamic string");
+    let count = lowercase.ref_count().unwrap();
+    let same = lowercase.clone().into_ascii_lowercase();
+    assert!(same.ptr_eq(&lowercase));
+    assert_eq!(lowercase.ref_count(), Some(count + 1));
+}
+
 #[test]
 fn test_ascii_lowercase() {
     assert_eq!(Atom::from("").to_ascii_lowercase(), Atom::from(""));
@@ -296,10 +878,586 @@ fn test_eq_ignore_ascii_case() {
 }
 
 #[test]
+#[allow(clippy::cmp_owned)]
+fn test_eq_str_ref_and_char() {
+    let head = Atom::from("head");
+    assert!(head == "head");
+    assert!("head" == head);
+    assert!(head != "body");
+    assert!("body" != head);
+    let dynamic = Atom::from("a dynamic string");
+    assert!(dynamic == "a dynamic string");
+    assert!(Atom::from("defaults") == "defaults");
+
+    assert!(Atom::from('a') == 'a');
+    assert!('a' == Atom::from("a"));
+    assert!(Atom::from("❤") == '❤');
+    assert!(Atom::from("ab") != 'a');
+    assert!(Atom::from("") != 'a');
+    assert!('b' != Atom::from("a"));
+}
+
+#[test]
+#[allow(clippy::cmp_owned)]
 fn test_from_string() {
     assert!(Atom::from("camembert".to_owned()) == Atom::from("camembert"));
 }
 
+#[test]
+fn test_from_boxed_and_arc_str() {
+    use std::sync::Arc;
+
+    for s in &["", "defaults", "body", "a boxed string to intern"] {
+        let boxed: Box<str> = (*s).into();
+        assert_eq!(Atom::from(boxed), Atom::from(*s));
+        let arc: Arc<str> = (*s).into();
+        assert_eq!(Atom::from(arc), Atom::from(*s));
+    }
+    assert!(Atom::from(Box::<str>::from("defaults")).is_static());
+    assert!(Atom::from(Box::<str>::from("body")).is_inline());
+    assert!(Atom::from(Box::<str>::from("another boxed string")).is_dynamic());
+}
+
+#[test]
+fn test_from_char() {
+    for c in &['a', 'é', '❤', '💯'] {
+        let atom = Atom::from(*c);
+        assert!(atom.is_inline());
+        assert_eq!(atom, Atom::from(c.to_string()));
+    }
+    assert_eq!(Atom::from('a'), test_atom!("a"));
+    assert_eq!(Atom::from('❤'), test_atom!("❤"));
+}
+
+#[test]
+fn test_string_extend() {
+    let atoms = [
+        Atom::from("defaults"),
+        Atom::from("body"),
+        Atom::from(""),
+        Atom::from("a dynamic string"),
+    ];
+    let mut string = String::from("<");
+    string.extend(&atoms);
+    let mut expected = String::from("<");
+    for atom in atoms.iter() {
+        expected.push_str(atom.as_ref());
+    }
+    assert_eq!(string, expected);
+    assert_eq!(string, "<defaultsbodya dynamic string");
+}
+
+#[test]
+fn test_try_from_bytes() {
+    use std::collections::HashMap;
+    use std::convert::TryFrom;
+
+    assert_eq!(
+        Atom::try_from(&b"defaults"[..]).unwrap(),
+        test_atom!("defaults")
+    );
+    assert_eq!(Atom::try_from(&b"body"[..]).unwrap(), test_atom!("body"));
+    assert_eq!(
+        &*Atom::try_from(&b"a dynamic string"[..]).unwrap(),
+        "a dynamic string"
+    );
+    assert!(Atom::try_from(&b"\xFF\xFE"[..]).is_err());
+
+    let mut map: HashMap<Atom, i32> = HashMap::new();
+    map.insert("dynamic key".into(), 1);
+    let buf = b"xxdynamic keyxx";
+    assert_eq!(map.get(&Atom::try_from(&buf[2..13]).unwrap()), Some(&1));
+    assert_eq!(map.get(&"dynamic key".into()), Some(&1));
+}
+
+#[test]
+fn test_into_boxed_str() {
+    assert_eq!(&*test_atom!("defaults").into_boxed_str(), "defaults");
+    assert_eq!(&*Atom::from("body").into_boxed_str(), "body");
+
+    let d0 = Atom::from("a boxed dynamic string");
+    let d1 = d0.clone();
+    let boxed: Box<str> = d1.into();
+    assert_eq!(&*boxed, "a boxed dynamic string");
+    // The entry is still alive and shared with `d0`.
+    assert_eq!(&*d0, "a boxed dynamic string");
+    assert_eq!(d0, Atom::from("a boxed dynamic string"));
+    drop(d0);
+    assert_eq!(&*boxed, "a boxed dynamic string");
+    assert!(Atom::from("a boxed dynamic string").is_dynamic());
+}
+
+#[test]
+fn test_ref_count() {
+    assert_eq!(test_atom!("defaults").ref_count(), None);
+    assert_eq!(Atom::from("body").ref_count(), None);
+
+    let d0 = Atom::from("a string only this test interns");
+    let base = d0.ref_count().unwrap();
+    assert!(base >= 1);
+    let d1 = d0.clone();
+    assert_eq!(d0.ref_count(), Some(base + 1));
+    let d2 = Atom::from("a string only this test interns");
+    assert_eq!(d1.ref_count(), Some(base + 2));
+    drop(d1);
+    drop(d2);
+    assert_eq!(d0.ref_count(), Some(base));
+}
+
+#[test]
+fn test_reintern() {
+    for s in &["", "defaults", "body", "a reinterned dynamic string"] {
+        let atom = Atom::from(*s);
+        let reinterned = atom.reintern();
+        assert_eq!(reinterned, atom);
+        assert_eq!(reinterned.unsafe_data(), atom.unsafe_data());
+    }
+
+    // The empty string is always static, so an inline empty atom is not canonical.
+    let non_canonical = Atom::pack_inline(0, 0);
+    assert!(non_canonical.is_inline());
+    assert_eq!(&*non_canonical, "");
+    let canonical = non_canonical.reintern();
+    assert!(canonical.is_static());
+    assert_eq!(canonical, test_atom!(""));
+}
+
+#[test]
+fn test_from_no_alloc() {
+    assert_eq!(Atom::from_no_alloc("").unwrap(), test_atom!(""));
+    assert_eq!(Atom::from_no_alloc("body").unwrap(), test_atom!("body"));
+    assert!(Atom::from_no_alloc("zzz").unwrap().is_inline());
+    assert_eq!(
+        Atom::from_no_alloc("defaults").unwrap(),
+        test_atom!("defaults")
+    );
+    assert!(Atom::from_no_alloc("defaults").unwrap().is_static());
+
+    let err = Atom::from_no_alloc("not in the static table").unwrap_err();
+    assert_eq!(err, string_cache::NotInternable { len: 23 });
+    assert!(err.to_string().contains("23 bytes"));
+}
+
+#[test]
+fn test_from_cstr_safe() {
+    assert_eq!(Atom::from_cstr_safe("body").unwrap(), test_atom!("body"));
+    assert_eq!(
+        Atom::from_cstr_safe("a dynamic string").unwrap(),
+        Atom::from("a dynamic string")
+    );
+
+    let err = Atom::from_cstr_safe("nul\0byte").unwrap_err();
+    assert_eq!(err, string_cache::InteriorNul { position: 3 });
+    assert_eq!(err.to_string(), "nul byte found at position 3");
+    assert_eq!(
+        Atom::from_cstr_safe("\0").unwrap_err(),
+        string_cache::InteriorNul { position: 0 }
+    );
+}
+
+#[test]
+fn test_from_iter_dedup() {
+    let strings: Vec<String> = (0..500)
+        .map(|i| format!("batch interned string {}", i % 300))
+        .chain(vec!["".into(), "defaults".into(), "body".into()])
+        .collect();
+    let batch = Atom::from_iter_dedup(&strings);
+    assert_eq!(batch.len(), strings.len());
+    for (atom, string) in batch.iter().zip(&strings) {
+        let individual = Atom::from(&**string);
+        assert_eq!(atom.unsafe_data(), individual.unsafe_data());
+        assert_eq!(&**atom, &**string);
+    }
+    assert!(batch[500].is_static());
+    assert!(batch[501].is_static());
+    assert!(batch[502].is_inline());
+    assert!(batch[0].is_dynamic());
+}
+
+#[test]
+fn test_deserialize_into() {
+    #[derive(serde::Deserialize)]
+    struct Element {
+        #[serde(deserialize_with = "string_cache::deserialize_into::<TestAtomStaticSet, _>")]
+        name: TestAtom,
+    }
+
+    let element: Element = serde_json::from_str(r#"{"name": "font-weight"}"#).unwrap();
+    assert!(element.name.is_static());
+    assert_eq!(element.name, test_atom!("font-weight"));
+
+    let element: Element = serde_json::from_str(r#"{"name": "not a known atom"}"#).unwrap();
+    assert!(element.name.is_dynamic());
+    assert_eq!(&*element.name, "not a known atom");
+}
+
+#[test]
+fn test_static_index_in() {
+    use string_cache::static_index_in;
+
+    fn check<Static: StaticAtomSet>(string: &str) {
+        let index = static_index_in::<Static>(string).unwrap();
+        assert_eq!(Static::get().atoms[index as usize], string);
+    }
+    for string in &["", "defaults", "font-weight"] {
+        check::<TestAtomStaticSet>(string);
+        check::<OtherAtomStaticSet>(string);
+    }
+    check::<OtherAtomStaticSet>("svg-only-atom");
+
+    let index = static_index_in::<TestAtomStaticSet>("font-weight").unwrap();
+    assert_eq!(
+        u64::from(index),
+        test_atom!("font-weight").unsafe_data() >> 32
+    );
+    let index = static_index_in::<OtherAtomStaticSet>("font-weight").unwrap();
+    assert_eq!(
+        u64::from(index),
+        OtherAtom::from("font-weight").unsafe_data() >> 32
+    );
+
+    assert_eq!(static_index_in::<TestAtomStaticSet>("svg-only-atom"), None);
+    assert_eq!(
+        static_index_in::<OtherAtomStaticSet>("not in any set"),
+        None
+    );
+    // Inline strings are never in a static set.
+    assert_eq!(static_index_in::<OtherAtomStaticSet>("body"), None);
+}
+
+#[test]
+fn test_is_ascii() {
+    for string in [
+        "",
+        "a",
+        "address",
+        "font-weight",
+        "a dynamic string",
+        "é",
+        "abcdeé",
+        "\u{7f}",
+        "font-wéight",
+        "❤💯❤💯",
+        "a dynamic string with a non-ASCII ending é",
+    ] {
+        let atom = Atom::from(string);
+        assert_eq!(atom.is_ascii(), string.is_ascii(), "{:?}", string);
+    }
+    assert!(Atom::from("address").is_inline());
+    assert!(Atom::from("abcdeé").is_inline());
+    assert!(Atom::from("font-weight").is_static());
+    assert!(Atom::from("a dynamic string").is_dynamic());
+}
+
+#[test]
+fn from_pre_hashed() {
+    fn stored_hash(string: &str) -> u32 {
+        let hashes = phf_shared::hash(string, &0);
+        hashes.g ^ hashes.f1
+    }
+
+    for string in [
+        "",
+        "body",
+        "font-weight",
+        "a pre-hashed dynamic string",
+        "a pre-hashed dynamic string longer than every static atom",
+    ] {
+        let atom = unsafe { Atom::from_pre_hashed(string, stored_hash(string)) };
+        assert_eq!(atom.unsafe_data(), Atom::from(string).unsafe_data());
+        assert_eq!(atom, string);
+        if !atom.is_inline() {
+            assert_eq!(atom.get_hash(), stored_hash(string));
+        }
+    }
+    assert!(
+        unsafe { Atom::from_pre_hashed("font-weight", stored_hash("font-weight")) }.is_static()
+    );
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "wrong hash")]
+fn from_pre_hashed_checks_the_hash() {
+    let _ = unsafe { Atom::from_pre_hashed("a dynamic string with a wrong hash", 0) };
+}
+
+#[test]
+fn test_join_atoms() {
+    use string_cache::join_atoms;
+
+    let atoms = vec![
+        Atom::from("font-weight"),
+        Atom::from("a"),
+        Atom::from("a dynamic string longer than every static atom"),
+    ];
+    let joined = join_atoms(atoms, " ");
+    assert_eq!(
+        joined,
+        "font-weight a a dynamic string longer than every static atom"
+    );
+    assert_eq!(joined.capacity(), joined.len());
+
+    assert_eq!(join_atoms(vec![Atom::from("one")], ", "), "one");
+    assert_eq!(join_atoms(Vec::<Atom>::new(), ", "), "");
+    assert_eq!(join_atoms("a b c".split(' ').map(Atom::from), ""), "abc");
+}
+
+#[test]
+fn test_from_static_index() {
+    let index = (test_atom!("defaults").unsafe_data() >> 32) as u32;
+    assert_eq!(Atom::from_static_index(index), Some(test_atom!("defaults")));
+    assert_eq!(Atom::from_static_index(TEST_ATOM_COUNT), None);
+}
+
+#[test]
+fn test_serde_round_trip() {
+    for s in &["", "defaults", "body", "a serialized dynamic string"] {
+        let atom = Atom::from(*s);
+        let json = serde_json::to_string(&atom).unwrap();
+        // Human-readable formats always get the string.
+        assert_eq!(json, serde_json::to_string(s).unwrap());
+        assert_eq!(serde_json::from_str::<Atom>(&json).unwrap(), atom);
+
+        let bytes = bincode::serialize(&atom).unwrap();
+        assert_eq!(bincode::deserialize::<Atom>(&bytes).unwrap(), atom);
+    }
+}
+
+#[test]
+fn test_serde_borrowed_and_owned_strings() {
+    use serde::de::value::{BorrowedStrDeserializer, Error, StrDeserializer, StringDeserializer};
+    use serde::Deserialize;
+
+    for s in &["", "defaults", "body", "a deserialized dynamic string"] {
+        let atom = Atom::from(*s);
+        let borrowed = Atom::deserialize(BorrowedStrDeserializer::<Error>::new(s)).unwrap();
+        let transient = Atom::deserialize(StrDeserializer::<Error>::new(s)).unwrap();
+        let owned = Atom::deserialize(StringDeserializer::<Error>::new(s.to_string())).unwrap();
+        for deserialized in &[borrowed, transient, owned] {
+            assert_eq!(*deserialized, atom);
+            assert_eq!(deserialized.unsafe_data(), atom.unsafe_data());
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "serde_compact")]
+fn test_serde_compact() {
+    let atom = test_atom!("font-weight");
+    let bytes = bincode::serialize(&atom).unwrap();
+    // A u32 variant tag and a u32 index.
+    assert_eq!(bytes.len(), 8);
+    assert_eq!(bincode::deserialize::<Atom>(&bytes).unwrap(), atom);
+
+    let mut out_of_range = bincode::serialize(&test_atom!("")).unwrap();
+    out_of_range[4..].copy_from_slice(&TEST_ATOM_COUNT.to_le_bytes());
+    assert!(bincode::deserialize::<Atom>(&out_of_range).is_err());
+}
+
+#[test]
+fn test_try_inline_exact() {
+    let atom = Atom::try_inline_exact("1234567").unwrap();
+    assert!(atom.is_inline());
+    assert_eq!(atom, Atom::from("1234567"));
+    assert_eq!(Atom::try_inline_exact("12345678"), Err("12345678"));
+    // Too long to be inline, even though it is in the static set.
+    assert_eq!(Atom::try_inline_exact("defaults"), Err("defaults"));
+    assert_eq!(Atom::try_inline_exact("").unwrap(), test_atom!(""));
+}
+
+#[test]
+fn test_as_u64_round_trip() {
+    let atoms = [
+        test_atom!("defaults"),
+        Atom::from("body"),
+        Atom::from(""),
+        Atom::from("a dynamic atom round-tripped through u64"),
+    ];
+    for atom in atoms.iter() {
+        let bits = atom.as_u64();
+        assert_eq!(bits, atom.unsafe_data());
+        let copy = unsafe { Atom::from_u64(bits) };
+        assert_eq!(copy, *atom);
+        assert_eq!(&*copy, &**atom);
+    }
+
+    let dynamic = &atoms[3];
+    let count = dynamic.ref_count().unwrap();
+    let copy = unsafe { Atom::from_u64(dynamic.as_u64()) };
+    assert_eq!(dynamic.ref_count(), Some(count + 1));
+    drop(copy);
+    assert_eq!(dynamic.ref_count(), Some(count));
+}
+
+#[test]
+fn into_raw_from_raw() {
+    for string in [
+        "defaults",
+        "body",
+        "",
+        "a dynamic atom passed through into_raw",
+    ] {
+        let atom = Atom::from(string);
+        let count = atom.ref_count();
+        let bits = atom.clone().into_raw();
+        assert_eq!(bits, atom.as_u64());
+        // The number keeps its reference to a dynamic entry.
+        assert_eq!(atom.ref_count(), count.map(|count| count + 1));
+        let back = unsafe { Atom::from_raw(bits) };
+        assert_eq!(atom.ref_count(), count.map(|count| count + 1));
+        assert_eq!(back, atom);
+        drop(back);
+        assert_eq!(atom.ref_count(), count);
+    }
+}
+
+#[test]
+fn test_full_hash() {
+    use string_cache::DefaultAtom;
+
+    for s in &[
+        "",
+        "body",
+        "defaults",
+        "font-weight",
+        "a dynamic string to hash",
+    ] {
+        let atom = Atom::from(*s);
+        assert_eq!(atom.full_hash(), Atom::from(*s).full_hash());
+        // Static in one set, dynamic or inline in the others.
+        assert_eq!(atom.full_hash(), DefaultAtom::from(*s).full_hash());
+        assert_eq!(atom.full_hash(), OtherAtom::from(*s).full_hash());
+    }
+    assert!(test_atom!("defaults").is_static());
+    assert!(DefaultAtom::from("defaults").is_dynamic());
+    assert_ne!(
+        Atom::from("body").full_hash(),
+        Atom::from("html").full_hash()
+    );
+}
+
+#[test]
+fn test_new_inline() {
+    for s in &["a", "body", "❤", "1234567"] {
+        let atom = Atom::new_inline(s).unwrap();
+        assert!(atom.is_inline());
+        assert_eq!(&*atom, *s);
+        assert_eq!(atom, Atom::from(*s));
+    }
+    assert_eq!(Atom::new_inline(""), None);
+    assert_eq!(Atom::new_inline("12345678"), None);
+    assert_eq!(Atom::new_inline("defaults"), None);
+}
+
+#[test]
+#[cfg(feature = "phf")]
+fn atoms_as_phf_keys() {
+    use phf_shared::{FmtConst, PhfHash};
+    use std::collections::hash_map::DefaultHasher;
+    use std::fmt;
+    use std::hash::Hasher;
+
+    struct Const<'a>(&'a Atom);
+    impl fmt::Display for Const<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.0.fmt_const(f)
+        }
+    }
+
+    // The same table as phf_codegen would build, and the lookup that phf::Map does.
+    let keys = [
+        test_atom!("font-weight"),
+        test_atom!("defaults"),
+        test_atom!("a"),
+    ];
+    let state = phf_generator::generate_hash(&keys);
+    for key in &keys {
+        let looked_up = Atom::from(&**key);
+        let hash = phf_shared::hash(&looked_up, &state.key);
+        let index = phf_shared::get_index(&hash, &state.disps, state.map.len());
+        assert_eq!(keys[state.map[index as usize]], looked_up);
+    }
+
+    let mut state = DefaultHasher::new();
+    test_atom!("font-weight").phf_hash(&mut state);
+    let mut expected = DefaultHasher::new();
+    expected.write_u32(test_atom!("font-weight").get_hash());
+    assert_eq!(state.finish(), expected.finish());
+
+    let index = test_atom!("font-weight").unsafe_data() >> 32;
+    assert_eq!(
+        Const(&test_atom!("font-weight")).to_string(),
+        format!("::string_cache::Atom::pack_static({})", index)
+    );
+    assert_eq!(
+        Const(&Atom::from("ab")).to_string(),
+        format!("::string_cache::Atom::pack_inline({}, 2)", 0x62_61_00)
+    );
+    assert_eq!(Atom::pack_inline(0x62_61_00, 2), Atom::from("ab"));
+}
+
+#[test]
+#[cfg(feature = "phf")]
+#[should_panic(expected = "has no constant form")]
+fn dynamic_atoms_have_no_phf_constant() {
+    use phf_shared::FmtConst;
+    use std::fmt;
+
+    struct Const(Atom);
+    impl fmt::Display for Const {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.0.fmt_const(f)
+        }
+    }
+    let _ = Const(Atom::from("a dynamic phf key")).to_string();
+}
+
+#[test]
+#[cfg(feature = "rkyv")]
+fn test_rkyv_round_trip() {
+    use rkyv::Deserialize;
+
+    #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+    struct Element {
+        name: Atom,
+        attributes: Vec<Atom>,
+    }
+
+    let element = Element {
+        name: test_atom!("font-weight"),
+        attributes: vec![Atom::from("body"), Atom::from("an archived dynamic string")],
+    };
+    let bytes = rkyv::to_bytes::<_, 256>(&element).unwrap();
+    let archived = unsafe { rkyv::archived_root::<Element>(&bytes) };
+    assert_eq!(archived.name.as_str(), "font-weight");
+    assert_eq!(
+        archived.attributes[1].as_str(),
+        "an archived dynamic string"
+    );
+
+    let deserialized: Element = archived.deserialize(&mut rkyv::Infallible).unwrap();
+    assert!(deserialized.name.is_static());
+    assert_eq!(deserialized.name, element.name);
+    assert_eq!(deserialized.attributes, element.attributes);
+}
+
+#[test]
+#[cfg(feature = "borsh")]
+fn test_borsh_round_trip() {
+    for atom in &[
+        test_atom!("font-weight"),
+        Atom::from("body"),
+        Atom::from("a borsh dynamic string"),
+    ] {
+        let bytes = borsh::to_vec(atom).unwrap();
+        // Length-prefixed UTF-8, like a `String`.
+        assert_eq!(bytes, borsh::to_vec(&atom.to_string()).unwrap());
+        let deserialized: Atom = borsh::from_slice(&bytes).unwrap();
+        assert_eq!(deserialized, *atom);
+    }
+}
+
 #[test]
 fn test_try_static() {
     assert!(Atom::try_static("defaults").is_some());
@@ -307,6 +1465,556 @@ fn test_try_static() {
     assert!(Atom::try_static("not in the static table").is_none());
 }
 
+#[test]
+fn codegen_extend_from() {
+    let mut html = string_cache_codegen::AtomType::new("HtmlAtom", "html_atom!");
+    html.atoms(&["div", "span", "shared-atom"]);
+    let mut svg = string_cache_codegen::AtomType::new("SvgAtom", "svg_atom!");
+    svg.atoms(&["circle", "lineargradient", "shared-atom"]);
+
+    let mut merged = string_cache_codegen::AtomType::new("MergedAtom", "merged_atom!");
+    merged.extend_from(&html).extend_from(&svg);
+    let generated = merged.write_to_string().unwrap();
+
+    for atom in &["div", "span", "circle", "lineargradient"] {
+        assert!(
+            generated.contains(&format!("\"{}\"", atom)),
+            "{} missing",
+            atom
+        );
+    }
+    assert_eq!(generated.matches("(\"shared-atom\")").count(), 1);
+}
+
+#[test]
+fn codegen_write_to_string() {
+    let mut atoms = string_cache_codegen::AtomType::new("StringAtom", "string_atom!");
+    atoms.atoms(&["div", "a-long-keyword"]);
+    let generated = atoms.write_to_string().unwrap();
+    let mut written = Vec::new();
+    atoms.write_to(&mut written).unwrap();
+    assert_eq!(generated.as_bytes(), &*written);
+    assert!(generated.contains("pub type StringAtom"));
+
+    let error = atoms
+        .atom("div")
+        .deny_duplicates(true)
+        .write_to_string()
+        .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn codegen_atoms_from_file() {
+    let path = std::env::temp_dir().join(format!("atoms-from-file-{}.txt", std::process::id()));
+    std::fs::write(
+        &path,
+        "# keywords\ndiv\n\n  a-long-keyword  \r\n#not-an-atom\n",
+    )
+    .unwrap();
+    let mut from_file = string_cache_codegen::AtomType::new("FileAtom", "file_atom!");
+    let generated = from_file.atoms_from_file(&path).unwrap().write_to_string();
+    std::fs::remove_file(&path).unwrap();
+
+    let mut listed = string_cache_codegen::AtomType::new("FileAtom", "file_atom!");
+    listed.atoms(&["div", "a-long-keyword"]);
+    assert_eq!(generated.unwrap(), listed.write_to_string().unwrap());
+
+    match from_file.atoms_from_file(&path) {
+        Err(error) => assert_eq!(error.kind(), std::io::ErrorKind::NotFound),
+        Ok(_) => panic!("expected an error for a missing file"),
+    }
+}
+
+#[test]
+fn codegen_output_is_no_std() {
+    let mut atoms = string_cache_codegen::AtomType::new("CoreAtom", "core_atom!");
+    let generated = atoms
+        .atoms(&["div", "a-long-keyword"])
+        .with_all_constant(true)
+        .with_case_insensitive_macro("core_atom_ci!")
+        .with_debug_helpers(true)
+        .with_unknown_atom_error(true)
+        .with_values("u32", "core_atom_value", vec![("div", "1")])
+        .write_to_string()
+        .unwrap();
+    assert!(!generated.contains("std"), "{}", generated);
+}
+
+#[test]
+fn codegen_unknown_atom_error() {
+    let mut atoms = string_cache_codegen::AtomType::new("ErrorAtom", "error_atom!");
+    atoms.atoms(&["div", "a-long-keyword"]);
+    assert!(!atoms.write_to_string().unwrap().contains("compile_error"));
+    let generated = atoms
+        .with_unknown_atom_error(true)
+        .write_to_string()
+        .unwrap();
+    assert!(generated.contains("compile_error"));
+    assert!(generated.contains("is not a static atom in set ErrorAtom"));
+}
+
+#[test]
+fn codegen_is_deterministic() {
+    let atoms: Vec<String> = (0..200).map(|i| format!("keyword-{}", i)).collect();
+    let mut forward = string_cache_codegen::AtomType::new("SortedAtom", "sorted_atom!");
+    forward.atoms(&atoms).atoms(&["a", "b", "c"]);
+    let mut backward = string_cache_codegen::AtomType::new("SortedAtom", "sorted_atom!");
+    backward.atoms(&["c", "b", "a"]).atoms(atoms.iter().rev());
+    assert_eq!(
+        forward.write_to_string().unwrap(),
+        backward.write_to_string().unwrap()
+    );
+}
+
+#[test]
+fn codegen_deny_duplicates() {
+    let mut atoms = string_cache_codegen::AtomType::new("DupAtom", "dup_atom!");
+    atoms
+        .atoms(&["div", "a-long-keyword"])
+        .atom("span")
+        .atoms(&["a-long-keyword"]);
+    assert!(atoms.write_to(Vec::new()).is_ok());
+
+    let error = atoms
+        .deny_duplicates(true)
+        .write_to(Vec::new())
+        .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(error.to_string(), "duplicate atoms: [\"a-long-keyword\"]");
+}
+
+#[test]
+fn dynamic_set_stats() {
+    use string_cache::DefaultAtom;
+
+    // Atoms of every static set share the same dynamic set.
+    let _atoms = (
+        Atom::from("a dynamic atom for stats"),
+        OtherAtom::from("another dynamic atom for stats"),
+        DefaultAtom::from("a third dynamic atom for stats"),
+    );
+    let stats = string_cache::dynamic_set_stats();
+    assert_eq!(stats.buckets, 4096);
+    assert!(stats.occupied_buckets >= 1, "{:?}", stats);
+    assert!(stats.occupied_buckets <= stats.buckets, "{:?}", stats);
+    assert!(stats.longest_chain >= 1, "{:?}", stats);
+}
+
+#[test]
+fn atoms_from_reader() {
+    let input = "body\n\nfont-weight\r\na dynamic string\nlast\r";
+    let atoms: Vec<Atom> = string_cache::atoms_from_reader(input.as_bytes())
+        .collect::<std::io::Result<_>>()
+        .unwrap();
+    assert_eq!(
+        atoms,
+        ["body", "", "font-weight", "a dynamic string", "last\r"]
+    );
+
+    struct FailingReader;
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "failed",
+            ))
+        }
+    }
+    let mut atoms = string_cache::atoms_from_reader::<TestAtomStaticSet, _>(
+        std::io::BufReader::new(FailingReader),
+    );
+    assert_eq!(atoms.next().unwrap().unwrap_err().to_string(), "failed");
+}
+
+#[test]
+fn test_memory_report() {
+    let atoms: Vec<Atom> = (0..5)
+        .map(|i| Atom::from(format!("memory report atom {}", i)))
+        .collect();
+    let report = string_cache::memory_report();
+
+    let entries: usize = report
+        .strip_prefix("dynamic atoms: ")
+        .and_then(|rest| rest.split(' ').next())
+        .unwrap()
+        .parse()
+        .unwrap();
+    // Other tests may be interning concurrently.
+    assert!(entries >= atoms.len(), "{}", report);
+    assert!(report.contains("bucket fill: 0: "), "{}", report);
+}
+
+#[test]
+fn generated_atom_count() {
+    assert_eq!(
+        TEST_ATOM_COUNT as usize,
+        TestAtomStaticSet::get().atoms.len()
+    );
+    assert_eq!(TestAtomStaticSet::atom_count(), Some(TEST_ATOM_COUNT));
+}
+
+#[test]
+fn phf_str_set_get_index() {
+    let set = TestAtomStaticSet::get();
+    for (index, &string) in set.atoms.iter().enumerate() {
+        assert_eq!(set.get_index(string), Some(index as u32));
+        assert!(set.contains(string));
+        assert_eq!(Atom::from(string).static_index(), Some(index as u32));
+    }
+    for string in [
+        "body",
+        "svg-only-atom",
+        "a string longer than every static atom",
+    ] {
+        assert_eq!(set.get_index(string), None);
+        assert!(!set.contains(string));
+    }
+    assert!(string_cache::EmptyStaticAtomSet::get().contains(""));
+}
+
+#[test]
+fn static_set_max_len() {
+    let set = TestAtomStaticSet::get();
+    assert_eq!(set.max_len, "❤💯❤💯".len());
+    assert_eq!(
+        set.max_len,
+        set.atoms.iter().map(|s| s.len()).max().unwrap()
+    );
+    assert_eq!(OtherAtomStaticSet::get().max_len, "svg-only-atom".len());
+    assert_eq!(string_cache::EmptyStaticAtomSet::get().max_len, 0);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "regenerate your atom set")]
+fn mismatched_atom_count() {
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct StaleStaticSet;
+
+    impl StaticAtomSet for StaleStaticSet {
+        fn get() -> &'static string_cache::PhfStrSet {
+            TestAtomStaticSet::get()
+        }
+        fn empty_string_index() -> u32 {
+            TestAtomStaticSet::empty_string_index()
+        }
+        fn atom_count() -> Option<u32> {
+            Some(TEST_ATOM_COUNT + 1)
+        }
+    }
+
+    let _ = &*string_cache::Atom::<StaleStaticSet>::from("defaults");
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn canonical_atoms() {
+    for atom in [
+        Atom::from(""),
+        Atom::from("body"),
+        Atom::from("defaults"),
+        Atom::from("inline"),
+        Atom::from("a dynamic atom for debug_assert_canonical"),
+    ] {
+        atom.debug_assert_canonical();
+    }
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "non-canonical atom")]
+fn non_canonical_atom() {
+    // The empty string is always static, it is never stored inline.
+    let atom = Atom::pack_inline(0, 0);
+    assert_eq!(&*atom, "");
+    atom.debug_assert_canonical();
+}
+
+#[test]
+#[cfg(feature = "proptest")]
+fn proptest_arbitrary() {
+    use proptest::strategy::{Strategy, ValueTree};
+    use proptest::test_runner::TestRunner;
+
+    let strategy = proptest::arbitrary::any::<Atom>();
+    let mut runner = TestRunner::deterministic();
+    let (mut statics, mut inlines, mut dynamics) = (0, 0, 0);
+    for _ in 0..300 {
+        let atom = strategy.new_tree(&mut runner).unwrap().current();
+        assert_eq!(atom, Atom::from(&*atom));
+        if atom.is_static() {
+            statics += 1;
+        } else if atom.is_inline() {
+            inlines += 1;
+        } else {
+            dynamics += 1;
+        }
+    }
+    assert!(statics > 0 && inlines > 0 && dynamics > 0);
+}
+
+#[test]
+fn concat() {
+    let font = Atom::from("font");
+    let weight = Atom::from("-weight");
+    let font_weight = font.concat(&weight);
+    assert!(font_weight.is_static());
+    assert_eq!(font_weight, test_atom!("font-weight"));
+
+    let inline = Atom::from("ab").concat(&Atom::from("cd"));
+    assert!(inline.is_inline());
+    assert_eq!(inline, Atom::from("abcd"));
+
+    let dynamic = &font_weight + &Atom::from(" concatenated");
+    assert!(dynamic.is_dynamic());
+    assert_eq!(dynamic, Atom::from("font-weight concatenated"));
+
+    assert_eq!(Atom::from("").concat(&Atom::from("")), Atom::from(""));
+    assert_eq!(font.concat(&Atom::from("")), font);
+    assert_eq!(&Atom::from("é") + &Atom::from("ü"), Atom::from("éü"));
+}
+
+#[test]
+fn ascii_case_insensitive() {
+    use std::collections::HashMap;
+    use string_cache::AsciiCaseInsensitive;
+
+    let key = |s: &str| AsciiCaseInsensitive(Atom::from(s));
+    assert_eq!(key("DIV"), key("div"));
+    assert_eq!(key("Font-Weight"), key("font-weight"));
+    assert_eq!(
+        key("A Long Dynamic Attribute"),
+        key("a long DYNAMIC attribute")
+    );
+    assert_ne!(key("div"), key("span"));
+    assert_ne!(key("é"), key("É"));
+
+    let mut map = HashMap::new();
+    map.insert(key("DIV"), 1);
+    map.insert(key("font-weight"), 2);
+    map.insert(key("div"), 3);
+    assert_eq!(map.len(), 2);
+    assert_eq!(map[&key("Div")], 3);
+    assert_eq!(map[&key("FONT-WEIGHT")], 2);
+}
+
+#[test]
+fn generated_all_atoms() {
+    use crate::other::{OtherAtom, OtherAtomStaticSet, OTHER_ATOM_ALL};
+
+    let mut strings: Vec<&str> = OTHER_ATOM_ALL.iter().map(|atom| &**atom).collect();
+    strings.sort_unstable();
+    assert_eq!(
+        strings,
+        ["", "body", "defaults", "font-weight", "svg-only-atom"]
+    );
+
+    let static_len = OtherAtomStaticSet::get().atoms.len();
+    for (index, atom) in OTHER_ATOM_ALL.iter().enumerate() {
+        assert_eq!(*atom, OtherAtom::from(&**atom));
+        if index < static_len {
+            assert_eq!(*atom, OtherAtom::from_static_index(index as u32).unwrap());
+        } else {
+            assert!(atom.is_inline());
+        }
+    }
+}
+
+#[test]
+fn case_insensitive_macro() {
+    let matches = |name: &Atom| match *name {
+        ref name if test_atom_ci!("body", name) => 1,
+        ref name if test_atom_ci!("font-weight", name) => 2,
+        _ => 0,
+    };
+    assert_eq!(matches(&Atom::from("body")), 1);
+    assert_eq!(matches(&Atom::from("BODY")), 1);
+    assert_eq!(matches(&Atom::from("Font-Weight")), 2);
+    assert_eq!(matches(&test_atom!("font-weight")), 2);
+    assert_eq!(matches(&Atom::from("bodies")), 0);
+    assert!(test_atom_ci!("❤", &Atom::from("❤")));
+    assert!(!test_atom_ci!("a", &Atom::from("b")));
+}
+
+#[test]
+fn generated_values() {
+    use crate::other::{other_atom_value, OtherAtom};
+
+    assert_eq!(other_atom_value(&other_atom!("body")), Some(&("body", 1)));
+    assert_eq!(
+        other_atom_value(&OtherAtom::from("defaults")),
+        Some(&("defaults", 2))
+    );
+    assert_eq!(other_atom_value(&other_atom!("font-weight")), None);
+    assert_eq!(other_atom_value(&OtherAtom::from("")), None);
+    assert_eq!(other_atom_value(&OtherAtom::from("not in the set")), None);
+}
+
+#[test]
+fn generated_attributes() {
+    assert_eq!(format!("{:?}", OtherAtomStaticSet), "OtherAtomStaticSet");
+}
+
+#[test]
+fn generated_const_lookup() {
+    const NAMES: [&str; 4] = ["defaults", "body", "", "not-an-atom"];
+    const ATOMS: [Option<TestAtom>; 4] = [
+        test_atom_static(NAMES[0]),
+        test_atom_static(NAMES[1]),
+        test_atom_static(NAMES[2]),
+        test_atom_static(NAMES[3]),
+    ];
+    assert_eq!(ATOMS[0], Some(test_atom!("defaults")));
+    assert_eq!(ATOMS[1], Some(test_atom!("body")));
+    assert_eq!(ATOMS[2], Some(test_atom!("")));
+    assert_eq!(ATOMS[3], None);
+    assert_eq!(
+        test_atom_static(&String::from("❤💯")),
+        Some(test_atom!("❤💯"))
+    );
+}
+
+#[test]
+fn repeat() {
+    let indent = Atom::from("  ").repeat(3);
+    assert!(indent.is_inline());
+    assert_eq!(indent, Atom::from("      "));
+
+    let long = Atom::from("ab").repeat(10);
+    assert!(long.is_dynamic());
+    assert_eq!(long, Atom::from("ab".repeat(10)));
+
+    assert_eq!(test_atom!("defaults").repeat(1), test_atom!("defaults"));
+    assert_eq!(Atom::from("❤").repeat(2), Atom::from("❤❤"));
+    assert_eq!(Atom::from("body").repeat(0), Atom::from(""));
+    assert_eq!(Atom::from("").repeat(usize::MAX), Atom::from(""));
+}
+
+#[test]
+#[should_panic(expected = "capacity overflow")]
+fn repeat_overflow() {
+    Atom::from("ab").repeat(usize::MAX);
+}
+
+#[test]
+fn iter_static() {
+    let atoms: Vec<Atom> = Atom::iter_static().collect();
+    let strings: Vec<&str> = atoms.iter().map(|atom| &**atom).collect();
+    assert_eq!(strings, TestAtomStaticSet::get().atoms);
+    assert!(atoms.iter().all(Atom::is_static));
+    assert!(atoms.contains(&test_atom!("font-weight")));
+    assert!(atoms.contains(&test_atom!("")));
+    assert_eq!(string_cache::DefaultAtom::iter_static().count(), 1);
+}
+
+#[test]
+fn static_index_and_eq() {
+    let defaults = test_atom!("defaults");
+    let index = defaults.static_index().unwrap();
+    assert!(defaults.static_eq(index));
+    assert!(Atom::from("defaults").static_eq(index));
+    assert!(!test_atom!("font-weight").static_eq(index));
+    assert_eq!(Atom::from_static_index(index), Some(defaults));
+
+    assert_eq!(Atom::from("body").static_index(), None);
+    assert_eq!(Atom::from("a dynamic atom").static_index(), None);
+    for i in 0..TEST_ATOM_COUNT {
+        assert!(!Atom::from("body").static_eq(i));
+    }
+}
+
+#[test]
+fn heap_size_of_dynamic() {
+    assert_eq!(test_atom!("defaults").heap_size_of_dynamic(), 0);
+    assert_eq!(Atom::from("body").heap_size_of_dynamic(), 0);
+    assert_eq!(Atom::from("").heap_size_of_dynamic(), 0);
+    let dynamic = Atom::from("a dynamic string on the heap");
+    assert_eq!(dynamic.heap_size_of_dynamic(), dynamic.len());
+    assert_eq!(dynamic.clone().heap_size_of_dynamic(), dynamic.len());
+}
+
+#[test]
+fn debug_formatting() {
+    assert_eq!(
+        format!("{:?}", test_atom!("defaults")),
+        "Atom('defaults' type=static)"
+    );
+    assert_eq!(
+        format!("{:?}", Atom::from("body")),
+        "Atom('body' type=inline)"
+    );
+    assert_eq!(
+        format!("{:?}", Atom::from("a debug\tstring")),
+        "Atom('a debug\tstring' type=dynamic)"
+    );
+
+    assert_eq!(format!("{:#?}", test_atom!("defaults")), "\"defaults\"");
+    assert_eq!(
+        format!("{:#?}", Atom::from("a debug\tstring")),
+        "\"a debug\\tstring\""
+    );
+
+    #[derive(Debug)]
+    struct Element {
+        name: Atom,
+    }
+    let element = Element {
+        name: Atom::from("body"),
+    };
+    assert_eq!(
+        format!("{:#?}", element),
+        "Element {\n    name: \"body\",\n}"
+    );
+    assert!(element.name.is_inline());
+}
+
+#[test]
+fn atom_hasher() {
+    use std::collections::HashMap;
+    use string_cache::BuildAtomHasher;
+
+    let mut map: HashMap<Atom, usize, BuildAtomHasher> = HashMap::default();
+    let atoms: Vec<Atom> = (0..100)
+        .map(|i| Atom::from(format!("hashed atom {}", i)))
+        .chain(vec![test_atom!("defaults"), test_atom!("body")])
+        .collect();
+    for (i, atom) in atoms.iter().enumerate() {
+        map.insert(atom.clone(), i);
+    }
+    for (i, atom) in atoms.iter().enumerate() {
+        assert_eq!(map[atom], i);
+    }
+}
+
+#[test]
+#[cfg(feature = "dashmap")]
+fn atom_dash_map() {
+    use std::sync::Arc;
+
+    let map = Arc::new(string_cache::new_atom_dash_map::<Atom, u32>());
+    let threads: Vec<_> = (0_u32..4)
+        .map(|t| {
+            let map = map.clone();
+            thread::spawn(move || {
+                for i in 0..50 {
+                    map.insert(Atom::from(format!("thread {} key {}", t, i)), t * 100 + i);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(map.len(), 200);
+    for t in 0..4 {
+        for i in 0..50 {
+            let key = Atom::from(format!("thread {} key {}", t, i));
+            assert_eq!(*map.get(&key).unwrap(), t * 100 + i);
+        }
+    }
+}
+
 #[cfg(test)]
 #[path = "common-usage.rs"]
 mod common_usage;
@@ -0,0 +1,53 @@
+// Check the events reported to the intern observer.
+//
+// This is a separate test program rather than a `#[test] fn` among others,
+// since the observer is global and other tests would intern strings concurrently.
+#![allow(non_upper_case_globals)]
+
+use std::sync::Mutex;
+use string_cache::InternEvent;
+
+include!(concat!(env!("OUT_DIR"), "/test_atom.rs"));
+
+static EVENTS: Mutex<Vec<InternEvent>> = Mutex::new(Vec::new());
+
+fn take_events() -> Vec<InternEvent> {
+    std::mem::take(&mut *EVENTS.lock().unwrap())
+}
+
+fn main() {
+    string_cache::set_intern_observer(|event| EVENTS.lock().unwrap().push(event));
+
+    let _atoms = (
+        TestAtom::from(""),
+        TestAtom::from("defaults"),
+        TestAtom::from("body"),
+        TestAtom::from("an observed dynamic atom"),
+        TestAtom::from("an observed dynamic atom"),
+    );
+    assert_eq!(
+        take_events(),
+        [
+            InternEvent::Static { len: 0 },
+            InternEvent::Static { len: 8 },
+            InternEvent::Inline { len: 4 },
+            InternEvent::DynamicNew { len: 24 },
+            InternEvent::DynamicExisting { len: 24 },
+        ]
+    );
+
+    let _batch = TestAtom::from_iter_dedup(["font-weight", "a batch atom", "a batch atom"]);
+    assert_eq!(
+        take_events(),
+        [
+            InternEvent::Static { len: 11 },
+            InternEvent::DynamicNew { len: 12 },
+            InternEvent::DynamicExisting { len: 12 },
+        ]
+    );
+
+    // Replacing the observer.
+    string_cache::set_intern_observer(|_| {});
+    let _ = TestAtom::from("not observed");
+    assert_eq!(take_events(), []);
+}
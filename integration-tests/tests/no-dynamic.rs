@@ -0,0 +1,30 @@
+// Check that with the `no_dynamic` feature, static and inline atoms work
+// and creating an atom that would need the dynamic set panics.
+//
+// This is a separate test program since the feature would make most other tests panic.
+#![allow(non_upper_case_globals)]
+
+use std::panic;
+
+include!(concat!(env!("OUT_DIR"), "/test_atom.rs"));
+
+fn main() {
+    let atoms = TestAtom::from_iter_dedup(["", "font-weight", "body"]);
+    assert!(atoms[0].is_static() && atoms[1].is_static() && atoms[2].is_inline());
+    assert_eq!(atoms[1], test_atom!("font-weight"));
+    assert_eq!(atoms[2].clone(), TestAtom::from(String::from("body")));
+
+    let stats = string_cache::dynamic_set_stats();
+    assert_eq!(stats.occupied_buckets, 0);
+    assert_eq!(stats.longest_chain, 0);
+    assert_eq!(string_cache::shrink_dynamic_set(), 0);
+
+    panic::set_hook(Box::new(|_| {}));
+    for result in [
+        panic::catch_unwind(|| drop(TestAtom::from("not in the static set"))),
+        panic::catch_unwind(|| drop(TestAtom::from_iter_dedup(["body", "not in the static set"]))),
+    ] {
+        let message = *result.unwrap_err().downcast::<&str>().unwrap();
+        assert!(message.contains("`no_dynamic` feature"), "{}", message);
+    }
+}
@@ -0,0 +1,52 @@
+// Check that interning static or inline strings never touches the dynamic set.
+//
+// This is a separate test program rather than a `#[test] fn` among others
+// to make sure that no other test is creating dynamic atoms concurrently.
+#![allow(non_upper_case_globals)]
+
+include!(concat!(env!("OUT_DIR"), "/test_atom.rs"));
+
+fn dynamic_entries() -> usize {
+    string_cache::memory_report()
+        .strip_prefix("dynamic atoms: ")
+        .and_then(|rest| rest.split(' ').next())
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+fn main() {
+    assert_eq!(dynamic_entries(), 0);
+
+    let atoms: Vec<TestAtom> = [
+        "",
+        "defaults",
+        "font-weight",
+        "❤💯❤💯",
+        "a",
+        "body",
+        "zzzzzzz",
+    ]
+    .iter()
+    .map(|&s| TestAtom::from(s))
+    .collect();
+    assert!(atoms.iter().all(|atom| !atom.is_dynamic()));
+    assert_eq!(dynamic_entries(), 0);
+
+    let dynamic = TestAtom::from("not in the static table");
+    assert_eq!(dynamic_entries(), 1);
+    drop(dynamic);
+    // The per-thread cache keeps a reference to recently interned entries.
+    let cached = if cfg!(feature = "thread_local_cache") {
+        1
+    } else {
+        0
+    };
+    assert_eq!(dynamic_entries(), cached);
+
+    // Entries cached by a thread are released when it exits.
+    std::thread::spawn(|| drop(TestAtom::from("interned in another thread")))
+        .join()
+        .unwrap();
+    assert_eq!(dynamic_entries(), cached);
+}
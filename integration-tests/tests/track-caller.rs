@@ -0,0 +1,39 @@
+// Check that a misused static atom panics with the location of the caller.
+//
+// This is a separate test program rather than a `#[test] fn` among others
+// since it replaces the process-wide panic hook.
+#![allow(non_upper_case_globals)]
+
+use std::panic;
+use std::sync::{Arc, Mutex};
+
+include!(concat!(env!("OUT_DIR"), "/test_atom.rs"));
+
+fn main() {
+    let location = Arc::new(Mutex::new(None));
+    let hook_location = location.clone();
+    panic::set_hook(Box::new(move |info| {
+        let message = info
+            .payload()
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_default();
+        let line = info.location().unwrap().line();
+        *hook_location.lock().unwrap() = Some((message, line));
+    }));
+
+    // An index far beyond the end of the set, as if the atom was meant for another set.
+    let atom = TestAtom::pack_static(TEST_ATOM_COUNT + 1000);
+    let result = panic::catch_unwind(|| atom.len());
+    let expected_line = line!() - 1;
+    assert!(result.is_err());
+    let (message, line) = location.lock().unwrap().take().unwrap();
+    assert!(message.contains("different StaticAtomSet"), "{}", message);
+    assert_eq!(line, expected_line);
+
+    let result = panic::catch_unwind(|| atom.get_hash());
+    let expected_line = line!() - 1;
+    assert!(result.is_err());
+    let (_, line) = location.lock().unwrap().take().unwrap();
+    assert_eq!(line, expected_line);
+}
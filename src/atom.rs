@@ -7,21 +7,26 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use crate::builder::AtomBuilder;
 use crate::dynamic_set::{dynamic_set, Entry};
-use crate::static_sets::StaticAtomSet;
+use crate::static_sets::{lookup_static_set, static_set, StaticAtomSet};
 use debug_unreachable::debug_unreachable;
 
-use std::borrow::Cow;
-use std::cmp::Ordering::{self, Equal};
-use std::fmt;
-use std::hash::{Hash, Hasher};
-use std::marker::PhantomData;
-use std::mem;
-use std::num::NonZeroU64;
-use std::ops;
-use std::slice;
-use std::str;
-use std::sync::atomic::Ordering::SeqCst;
+use alloc::borrow::{Cow, ToOwned};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering::{self, Equal};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+use core::num::NonZeroU64;
+use core::ops;
+use core::ptr::NonNull;
+use core::slice;
+use core::str;
+use core::sync::atomic::Ordering::SeqCst;
 
 const DYNAMIC_TAG: u8 = 0b_00;
 const INLINE_TAG: u8 = 0b_01; // len in upper nybble
@@ -30,7 +35,13 @@ const TAG_MASK: u64 = 0b_11;
 const LEN_OFFSET: u64 = 4;
 const LEN_MASK: u64 = 0xF0;
 
-const MAX_INLINE_LEN: usize = 7;
+pub(crate) const MAX_INLINE_LEN: usize = 7;
+/// The fixed key for `Atom::full_hash`, which must not depend on the static set.
+const FULL_HASH_KEY: phf_shared::HashKey = 0x5354_5249_4e47_4341;
+/// The fixed key for the hashes stored in static sets and dynamic entries,
+/// so that `get_hash` only depends on the string. `string_cache_codegen` uses the same key.
+/// These hashes are therefore predictable, and the dynamic set doesn't place entries by them.
+const STORED_HASH_KEY: phf_shared::HashKey = 0;
 const STATIC_SHIFT_BITS: usize = 32;
 
 /// Represents a string that has been interned.
@@ -52,6 +63,10 @@ const STATIC_SHIFT_BITS: usize = 32;
 /// `Atom::from(String)`.  These create a reference-counted atom which will be
 /// automatically freed when all references to it are dropped.
 ///
+/// Only strings that need such a reference-counted entry touch the global dynamic set.
+/// Strings in the static set and strings of up to 7 bytes, which are stored inline in the
+/// atom itself, are interned without taking any lock or allocating.
+///
 /// This means that your application can safely have a loop which tokenizes data, creates
 /// atoms from the tokens, and compares the atoms to a predefined set of keywords, without
 /// running the risk of arbitrary memory consumption from creating large numbers of atoms —
@@ -82,6 +97,43 @@ pub struct Atom<Static> {
     phantom: PhantomData<Static>,
 }
 
+/// The error returned by [`Atom::from_no_alloc`] for a string that would need
+/// a dynamic entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NotInternable {
+    /// The length in bytes of the string.
+    pub len: usize,
+}
+
+impl fmt::Display for NotInternable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a string of {} bytes is neither static nor short enough to be inline",
+            self.len
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for NotInternable {}
+
+/// The error returned by [`Atom::from_cstr_safe`] for a string containing a NUL byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InteriorNul {
+    /// The position in bytes of the first NUL byte.
+    pub position: usize,
+}
+
+impl fmt::Display for InteriorNul {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "nul byte found at position {}", self.position)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InteriorNul {}
+
 // FIXME: bound removed from the struct definition before of this error for pack_static:
 // "error[E0723]: trait bounds other than `Sized` on const fn parameters are unstable"
 // https://github.com/rust-lang/rust/issues/57563
@@ -90,6 +142,12 @@ impl<Static> Atom<Static> {
     #[inline(always)]
     #[doc(hidden)]
     pub const fn pack_static(n: u32) -> Self {
+        // Static sets have at most `u32::MAX` atoms, see `static_set`, and the index must
+        // come back out of the packed data whole.
+        debug_assert!(
+            ((n as u64) << STATIC_SHIFT_BITS) >> STATIC_SHIFT_BITS == n as u64,
+            "static atom index doesn't fit in the packed data"
+        );
         Self {
             unsafe_data: unsafe {
                 // STATIC_TAG ensures this is non-zero
@@ -130,6 +188,57 @@ impl<Static: StaticAtomSet> Atom<Static> {
         self.unsafe_data.get()
     }
 
+    /// Return the packed 64-bit representation of this atom.
+    ///
+    /// Depending on the kind of atom, this holds the string itself, the index of a static
+    /// atom, or the address of a dynamic entry. It can be turned back into an atom with
+    /// `from_u64`, but doesn't keep a dynamic entry alive: the atom must outlive the number.
+    pub fn as_u64(&self) -> u64 {
+        self.unsafe_data.get()
+    }
+
+    /// Return a new atom from the representation returned by `as_u64`.
+    ///
+    /// Like `clone()`, this takes a new reference to a dynamic entry,
+    /// so the original atom still has to be dropped as usual.
+    ///
+    /// # Safety
+    ///
+    /// `bits` must have been returned by `as_u64` on an `Atom<Static>` for the same `Static`,
+    /// and if that atom is dynamic, it must still be alive.
+    pub unsafe fn from_u64(bits: u64) -> Self {
+        let borrowed = mem::ManuallyDrop::new(Atom {
+            unsafe_data: NonZeroU64::new_unchecked(bits),
+            phantom: PhantomData,
+        });
+        Atom::clone(&borrowed)
+    }
+
+    /// Return the packed representation of this atom, transferring its ownership to the number.
+    ///
+    /// Unlike `as_u64`, a dynamic entry is kept alive until the number is turned back into
+    /// an atom with `from_raw`. This is the same as `as_u64` followed by `mem::forget`.
+    pub fn into_raw(self) -> u64 {
+        mem::ManuallyDrop::new(self).unsafe_data.get()
+    }
+
+    /// Take back ownership of an atom from the representation returned by `into_raw`.
+    ///
+    /// Unlike `from_u64`, this doesn't take a new reference to a dynamic entry:
+    /// the returned atom owns the reference that was given up by `into_raw`.
+    ///
+    /// # Safety
+    ///
+    /// `bits` must have been returned by `into_raw` on an `Atom<Static>` for the same `Static`,
+    /// or by `as_u64` on such an atom that was then forgotten with `mem::forget`.
+    /// Each such number must be passed to `from_raw` at most once.
+    pub unsafe fn from_raw(bits: u64) -> Self {
+        Atom {
+            unsafe_data: NonZeroU64::new_unchecked(bits),
+            phantom: PhantomData,
+        }
+    }
+
     /// Return true if this is a static Atom. For testing.
     #[doc(hidden)]
     pub fn is_static(&self) -> bool {
@@ -148,18 +257,89 @@ impl<Static: StaticAtomSet> Atom<Static> {
         self.tag() == INLINE_TAG
     }
 
-    fn static_index(&self) -> u64 {
+    /// The index of a static atom. Meaningless for other atoms.
+    pub(crate) fn raw_static_index(&self) -> u64 {
         self.unsafe_data.get() >> STATIC_SHIFT_BITS
     }
 
+    /// Return the index of this atom in the static set, or `None` if it is not static.
+    ///
+    /// Together with `static_eq`, this can be used to dispatch on static atoms with a
+    /// `match` on their index, such as a jump table.
+    #[inline]
+    pub fn static_index(&self) -> Option<u32> {
+        if self.is_static() {
+            Some(self.raw_static_index() as u32)
+        } else {
+            None
+        }
+    }
+
+    /// Return whether this is the static atom at `index` in the static set.
+    ///
+    /// This only compares the packed representation, without looking up the set or the string.
+    /// Indices are only meaningful within one `Static` set.
+    #[inline]
+    pub fn static_eq(&self, index: u32) -> bool {
+        self.unsafe_data == Self::pack_static(index).unsafe_data
+    }
+
+    /// Whether both atoms are the same interned value, comparing only their packed data.
+    ///
+    /// Equal strings are always interned the same way in a given set, so this gives the
+    /// same result as `==`, which is derived from the same comparison.
+    /// Unlike `cmp`, it never looks at the strings.
+    #[doc(alias = "cheap_eq")]
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.unsafe_data == other.unsafe_data
+    }
+
+    /// Compare atoms by their packed data rather than by their strings.
+    ///
+    /// This is a total order consistent with `==`, and much cheaper than `cmp`,
+    /// for when the order itself doesn't matter. It depends on where dynamic entries
+    /// are allocated, so it can differ between runs. See also [`ByIdentity`](crate::ByIdentity).
+    #[inline]
+    pub fn cmp_by_identity(&self, other: &Self) -> Ordering {
+        self.unsafe_data.cmp(&other.unsafe_data)
+    }
+
+    /// Whether this atom has the same string as an atom of another `StaticAtomSet`.
+    ///
+    /// Static atoms are packed differently in each set, so unlike `==` this can't just
+    /// compare packed data, and compares the strings unless both atoms are inline or dynamic.
+    /// Atoms that are equal this way also have the same `get_hash`.
+    pub fn eq_across<Other: StaticAtomSet>(&self, other: &Atom<Other>) -> bool {
+        if self.tag() == other.tag() && self.tag() != STATIC_TAG {
+            // Inline atoms don't depend on the set, and all sets share the dynamic set.
+            self.unsafe_data == other.unsafe_data
+        } else {
+            **self == **other
+        }
+    }
+
     /// Get the hash of the string as it is stored in the set.
+    ///
+    /// This only depends on the string: it is the same for any `StaticAtomSet`,
+    /// whether or not the string is static in that set. It is also the same in every
+    /// process, so anyone can find strings with equal hashes. `Hash` only hashes this value,
+    /// so keying a `HashMap` by atoms of untrusted strings doesn't protect against flooding,
+    /// even with its random key.
+    #[track_caller]
     pub fn get_hash(&self) -> u32 {
         match self.tag() {
             DYNAMIC_TAG => {
                 let entry = self.unsafe_data.get() as *const Entry;
                 unsafe { (*entry).hash }
             }
-            STATIC_TAG => Static::get().hashes[self.static_index() as usize],
+            STATIC_TAG => {
+                let hashes = static_set::<Static>().hashes;
+                match hashes.get(self.raw_static_index() as usize) {
+                    Some(&hash) => hash,
+                    None => static_index_out_of_range(self.raw_static_index(), hashes.len()),
+                }
+            }
             INLINE_TAG => {
                 let data = self.unsafe_data.get();
                 // This may or may not be great...
@@ -169,21 +349,237 @@ impl<Static: StaticAtomSet> Atom<Static> {
         }
     }
 
+    /// Get a 64-bit hash of the string, for use in external hash tables.
+    ///
+    /// Unlike `get_hash`, this only depends on the string: equal strings have equal full
+    /// hashes whether they are static, inline or dynamic, and for any `StaticAtomSet`.
+    /// It is also stable across runs and platforms. The string is hashed on each call.
+    pub fn full_hash(&self) -> u64 {
+        let hashes = phf_shared::hash(&**self, &FULL_HASH_KEY);
+        (u64::from(hashes.g) << 32) | u64::from(hashes.f1)
+    }
+
+    /// Return the current reference count of a dynamic atom, or `None` for other atoms.
+    ///
+    /// This is meant for debugging reference count leaks. Other threads may clone or drop
+    /// atoms for the same string at any time, so the count may already be stale.
+    pub fn ref_count(&self) -> Option<isize> {
+        if self.tag() == DYNAMIC_TAG {
+            let entry = self.unsafe_data.get() as *const Entry;
+            Some(unsafe { &*entry }.ref_count.load(SeqCst))
+        } else {
+            None
+        }
+    }
+
+    /// Return the size in bytes of the heap-allocated string of a dynamic atom,
+    /// or 0 for other atoms.
+    ///
+    /// The string is shared by all atoms for it, so adding this up for every atom
+    /// counts it once per reference. Divide by `ref_count`, or only count each entry once,
+    /// to avoid overestimating memory usage.
+    pub fn heap_size_of_dynamic(&self) -> usize {
+        if self.tag() == DYNAMIC_TAG {
+            let entry = self.unsafe_data.get() as *const Entry;
+            unsafe { &*entry }.string.len()
+        } else {
+            0
+        }
+    }
+
+    /// Intern the string of this atom again, returning the canonical atom for it.
+    ///
+    /// This is not what you want in order to copy an atom: `clone()` is the cheap path,
+    /// it only bumps the reference count of a dynamic entry and never hashes or locks.
+    /// `reintern` instead looks the string up again, just like `Atom::from(&*atom)`,
+    /// which can be used to canonicalize an atom that was built by other means,
+    /// for example through FFI.
+    pub fn reintern(&self) -> Self {
+        Atom::from(self.get_str())
+    }
+
+    /// Panic if this atom is not represented the way `Atom::from` would create it.
+    ///
+    /// Atoms built through FFI or with `pack_static` and `pack_inline` can end up
+    /// non-canonical, for example an inline atom for a string that is in the static set.
+    /// Such atoms compare unequal to atoms for the same string, so FFI boundaries and tests
+    /// can call this to catch them early.
+    #[cfg(debug_assertions)]
+    #[track_caller]
+    pub fn debug_assert_canonical(&self) {
+        let canonical = self.reintern();
+        assert!(
+            self.unsafe_data == canonical.unsafe_data,
+            "non-canonical atom for {:?}: found data {:#x}, expected {:#x}",
+            &**self,
+            self.unsafe_data,
+            canonical.unsafe_data,
+        );
+    }
+
+    /// Return the string of this atom.
+    ///
+    /// This is the same as `&*atom` or `atom.as_ref()`, without relying on type inference.
+    ///
+    /// `Atom` does not implement `Borrow<str>`: its `Hash` impl uses the precomputed hash,
+    /// which doesn't match the hash of the string, so a `HashMap<Atom, V>` can't be queried
+    /// with a `&str` directly. Look keys up with `map.get(&Atom::from(string))` instead, which
+    /// for a string that isn't in the map never needs to keep a dynamic entry.
+    #[doc(alias = "as_str")]
+    #[inline]
+    pub fn get_str(&self) -> &str {
+        self
+    }
+
+    /// Return the string of a static atom, which lives in the static set, or `None` for
+    /// other atoms.
+    #[track_caller]
+    pub fn as_static_str(&self) -> Option<&'static str> {
+        match self.tag() {
+            STATIC_TAG => {
+                let atoms = static_set::<Static>().atoms;
+                match atoms.get(self.raw_static_index() as usize) {
+                    Some(string) => Some(string),
+                    None => static_index_out_of_range(self.raw_static_index(), atoms.len()),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the string of this atom, borrowed if it is static and copied otherwise.
+    pub fn to_cow(&self) -> Cow<'static, str> {
+        match self.as_static_str() {
+            Some(string) => Cow::Borrowed(string),
+            None => Cow::Owned(String::from(&**self)),
+        }
+    }
+
+    /// Return the string of this atom with a `'static` lifetime, leaking memory if needed.
+    ///
+    /// Static atoms already live in the static set and leak nothing. Inline atoms are copied
+    /// into a leaked `Box<str>`, and dynamic atoms keep their reference to their entry forever,
+    /// so it is never freed. This is meant for initialization, not for use in a loop.
+    pub fn leak(self) -> &'static str {
+        if let Some(string) = self.as_static_str() {
+            return string;
+        }
+        match self.tag() {
+            DYNAMIC_TAG => {
+                let entry = self.unsafe_data.get() as *const Entry;
+                mem::forget(self);
+                unsafe { &(*entry).string }
+            }
+            _ => Box::leak(Box::from(&*self)),
+        }
+    }
+
+    /// Return a pointer to the UTF-8 bytes of this atom, like `str::as_ptr`.
+    ///
+    /// There are `self.len()` bytes, valid for as long as this atom is borrowed.
+    /// For inline atoms, the pointer is into the atom itself rather than into the heap,
+    /// so it is only valid while `self` is not moved, and must not be written through.
+    #[inline]
+    pub fn as_ptr(&self) -> *const u8 {
+        self.as_bytes().as_ptr()
+    }
+
+    /// Return the UTF-8 bytes of this atom.
+    ///
+    /// For inline atoms this slices the packed data directly.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        match self.as_inline_bytes() {
+            Some(bytes) => bytes,
+            None => (**self).as_bytes(),
+        }
+    }
+
+    /// Return the bytes packed in an inline atom, at most 7, or `None` for other atoms.
+    ///
+    /// These are the UTF-8 bytes of the string, without the padding after them.
+    #[doc(alias = "inline_bytes")]
+    #[inline]
+    pub fn as_inline_bytes(&self) -> Option<&[u8]> {
+        if self.tag() == INLINE_TAG {
+            let len = (self.unsafe_data() & LEN_MASK) >> LEN_OFFSET;
+            debug_assert!(len as usize <= MAX_INLINE_LEN);
+            Some(unsafe { inline_atom_slice(&self.unsafe_data).get_unchecked(..(len as usize)) })
+        } else {
+            None
+        }
+    }
+
+    /// Copy the contents of this atom out into an owned `Box<str>`.
+    ///
+    /// Dynamic entries are shared, so their string is cloned rather than moved out;
+    /// the atom is then dropped as usual, releasing its reference to the entry.
+    pub fn into_boxed_str(self) -> Box<str> {
+        Box::from(&*self)
+    }
+
+    /// Return the static atom at `index` in the static set, if the set is that large.
+    pub fn from_static_index(index: u32) -> Option<Self> {
+        if (index as usize) < static_set::<Static>().atoms.len() {
+            Some(Self::pack_static(index))
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over all the atoms of the static set, in the order of their index.
+    ///
+    /// This includes the empty atom. Short strings given to `string_cache_codegen`
+    /// are stored inline rather than in the static set, so they are not included.
+    pub fn iter_static() -> impl Iterator<Item = Self> {
+        (0..static_set::<Static>().atoms.len() as u32).map(Self::pack_static)
+    }
+
     pub fn try_static(string_to_add: &str) -> Option<Self> {
         Self::try_static_internal(string_to_add).ok()
     }
 
-    fn try_static_internal(string_to_add: &str) -> Result<Self, phf_shared::Hashes> {
-        let static_set = Static::get();
-        let hash = phf_shared::hash(&*string_to_add, &static_set.key);
-        let index = phf_shared::get_index(&hash, static_set.disps, static_set.atoms.len());
+    /// Return the static atom for this string, or the hash to store in its dynamic entry.
+    fn try_static_internal(string_to_add: &str) -> Result<Self, u32> {
+        let static_set = lookup_static_set::<Static>();
+        let hash = match static_set.get_index_or_hash(string_to_add) {
+            Ok(index) => return Ok(Self::pack_static(index)),
+            // Avoid hashing the string again.
+            Err(Some(hash)) if static_set.key == STORED_HASH_KEY => hash,
+            Err(_) => phf_shared::hash(string_to_add, &STORED_HASH_KEY),
+        };
+        Err(hash.g ^ hash.f1)
+    }
+}
 
-        if static_set.atoms[index as usize] == string_to_add {
-            Ok(Self::pack_static(index))
-        } else {
-            Err(hash)
+/// Return the index of a string in the static set `Static`, if it is there.
+///
+/// This only does the perfect hash lookup: no atom is created, and the dynamic set is never
+/// touched. Strings that are not in `Static`, including short strings that would be stored
+/// inline, return `None`.
+pub fn static_index_in<Static: StaticAtomSet>(string: &str) -> Option<u32> {
+    lookup_static_set::<Static>().get_index(string)
+}
+
+/// Join atoms into a `String`, with `sep` between each of them.
+///
+/// The atoms are collected first so the string can be allocated once with its full length.
+pub fn join_atoms<Static, I>(iter: I, sep: &str) -> String
+where
+    Static: StaticAtomSet,
+    I: IntoIterator<Item = Atom<Static>>,
+{
+    let atoms: Vec<Atom<Static>> = iter.into_iter().collect();
+    let len = atoms.iter().map(|atom| atom.len()).sum::<usize>()
+        + sep.len() * atoms.len().saturating_sub(1);
+    let mut joined = String::with_capacity(len);
+    for (i, atom) in atoms.iter().enumerate() {
+        if i > 0 {
+            joined.push_str(sep);
         }
+        joined.push_str(atom);
     }
+    joined
 }
 
 impl<Static: StaticAtomSet> Default for Atom<Static> {
@@ -203,34 +599,266 @@ impl<Static: StaticAtomSet> Hash for Atom<Static> {
     }
 }
 
-impl<'a, Static: StaticAtomSet> From<Cow<'a, str>> for Atom<Static> {
-    fn from(string_to_add: Cow<'a, str>) -> Self {
+impl<Static: StaticAtomSet> Atom<Static> {
+    /// Create an atom without ever allocating or locking the dynamic set.
+    ///
+    /// This succeeds for the empty string, for strings short enough to be stored inline,
+    /// and for strings in the static set. Other strings would need a dynamic entry,
+    /// so an error with their length is returned instead.
+    pub fn from_no_alloc(string_to_add: &str) -> Result<Self, NotInternable> {
+        let len = string_to_add.len();
+        if len == 0 {
+            Ok(Self::pack_static(Static::empty_string_index()))
+        } else if len <= MAX_INLINE_LEN {
+            Ok(Self::pack_inline_str(string_to_add))
+        } else {
+            Self::try_static_internal(string_to_add).map_err(|_| NotInternable { len })
+        }
+    }
+
+    /// Intern a string that can be passed to C as a NUL-terminated string,
+    /// or return the position of its first NUL byte.
+    ///
+    /// `Atom::from` accepts any string, but C code would see a string with a NUL byte
+    /// as truncated at that byte.
+    pub fn from_cstr_safe(string_to_add: &str) -> Result<Self, InteriorNul> {
+        match string_to_add.bytes().position(|b| b == 0) {
+            Some(position) => Err(InteriorNul { position }),
+            None => Ok(Self::intern(string_to_add)),
+        }
+    }
+
+    /// Create an inline atom, or give the string back if it is too long to be stored inline.
+    ///
+    /// This never allocates, locks, or looks up the static set, so a string that is too long
+    /// is rejected even if it is a static atom. The empty string gives the usual static
+    /// empty atom.
+    pub fn try_inline_exact(string_to_add: &str) -> Result<Self, &str> {
+        match string_to_add.len() {
+            0 => Ok(Self::pack_static(Static::empty_string_index())),
+            len if len <= MAX_INLINE_LEN => Ok(Self::pack_inline_str(string_to_add)),
+            _ => Err(string_to_add),
+        }
+    }
+
+    /// Return the atom for the string of `self` followed by that of `other`.
+    ///
+    /// When the result is short enough to be inline, it is built on the stack
+    /// without any allocation.
+    pub fn concat(&self, other: &Self) -> Self {
+        let (first, second) = (&**self, &**other);
+        let len = first.len() + second.len();
+        if len <= MAX_INLINE_LEN {
+            let mut buffer = [0; MAX_INLINE_LEN];
+            buffer[..first.len()].copy_from_slice(first.as_bytes());
+            buffer[first.len()..len].copy_from_slice(second.as_bytes());
+            // Concatenating two valid UTF-8 strings gives valid UTF-8.
+            Self::intern(unsafe { str::from_utf8_unchecked(&buffer[..len]) })
+        } else {
+            let mut string = String::with_capacity(len);
+            string.push_str(first);
+            string.push_str(second);
+            Self::intern(string)
+        }
+    }
+
+    /// Return the atom for the string of `self` repeated `n` times, like `str::repeat`.
+    ///
+    /// When the result is short enough to be inline, it is built on the stack
+    /// without any allocation.
+    ///
+    /// # Panics
+    ///
+    /// Like `str::repeat`, this panics if the length of the result would overflow.
+    pub fn repeat(&self, n: usize) -> Self {
+        let string: &str = self;
+        let len = string.len().checked_mul(n).expect("capacity overflow");
+        if len <= MAX_INLINE_LEN {
+            let mut buffer = [0; MAX_INLINE_LEN];
+            for chunk in buffer[..len].chunks_mut(string.len().max(1)) {
+                chunk.copy_from_slice(string.as_bytes());
+            }
+            // Repeating a valid UTF-8 string gives valid UTF-8.
+            Self::intern(unsafe { str::from_utf8_unchecked(&buffer[..len]) })
+        } else {
+            Self::intern(string.repeat(n))
+        }
+    }
+
+    /// Split this atom on ASCII whitespace and intern each part, as for a list of class names.
+    ///
+    /// Like `str::split_ascii_whitespace`, this gives no atoms for an empty or blank string.
+    /// Parts short enough to be inline don't allocate.
+    pub fn split_whitespace_atoms(&self) -> impl Iterator<Item = Self> + '_ {
+        self.split_ascii_whitespace().map(Self::from)
+    }
+
+    /// Intern the string made of these chars, like `Atom::from(iter.collect::<String>())`.
+    ///
+    /// The chars are written to a buffer on the stack while they fit in an inline atom,
+    /// so short strings, such as decoded character references, don't allocate.
+    pub fn from_char_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        use core::fmt::Write;
+
+        let mut builder = AtomBuilder::new();
+        for c in iter {
+            // Writing to an `AtomBuilder` never fails.
+            let _ = builder.write_char(c);
+        }
+        builder.finish()
+    }
+
+    /// Create an inline atom, or return `None` if the string can't be stored inline.
+    ///
+    /// Unlike `try_inline_exact`, the returned atom is always inline: the empty string
+    /// is represented as a static atom, so it gives `None`, as do strings over 7 bytes.
+    /// Either way, this never allocates or touches the dynamic set.
+    pub fn new_inline(string: &str) -> Option<Self> {
+        if !string.is_empty() && string.len() <= MAX_INLINE_LEN {
+            Some(Self::pack_inline_str(string))
+        } else {
+            None
+        }
+    }
+
+    fn pack_inline_str(string_to_add: &str) -> Self {
+        let len = string_to_add.len();
+        debug_assert!(0 < len && len <= MAX_INLINE_LEN);
+        let mut data: u64 = (INLINE_TAG as u64) | ((len as u64) << LEN_OFFSET);
+        {
+            let dest = inline_atom_slice_mut(&mut data);
+            dest[..len].copy_from_slice(string_to_add.as_bytes());
+        }
+        Atom {
+            // INLINE_TAG ensures this is never zero
+            unsafe_data: unsafe { NonZeroU64::new_unchecked(data) },
+            phantom: PhantomData,
+        }
+    }
+
+    /// Intern a string that can be turned into the `Box<str>` of a dynamic entry.
+    ///
+    /// The conversion only happens if a new entry is needed,
+    /// so owned strings are moved into the set without copying.
+    pub(crate) fn intern<S>(string_to_add: S) -> Self
+    where
+        S: ops::Deref<Target = str> + Into<Box<str>>,
+    {
         let len = string_to_add.len();
         if len == 0 {
+            #[cfg(feature = "intern_observer")]
+            crate::observer::observe(crate::InternEvent::Static { len });
             Self::pack_static(Static::empty_string_index())
         } else if len <= MAX_INLINE_LEN {
-            let mut data: u64 = (INLINE_TAG as u64) | ((len as u64) << LEN_OFFSET);
-            {
-                let dest = inline_atom_slice_mut(&mut data);
-                dest[..len].copy_from_slice(string_to_add.as_bytes());
+            #[cfg(feature = "intern_observer")]
+            crate::observer::observe(crate::InternEvent::Inline { len });
+            Self::pack_inline_str(&string_to_add)
+        } else {
+            match Self::try_static_internal(&string_to_add) {
+                Ok(atom) => {
+                    #[cfg(feature = "intern_observer")]
+                    crate::observer::observe(crate::InternEvent::Static { len });
+                    atom
+                }
+                Err(hash) => Self::from_entry(dynamic_set().insert(string_to_add, hash)),
             }
-            Atom {
-                // INLINE_TAG ensures this is never zero
-                unsafe_data: unsafe { NonZeroU64::new_unchecked(data) },
-                phantom: PhantomData,
+        }
+    }
+
+    /// Like `Atom::from`, but with the hash of the string computed by the caller.
+    ///
+    /// The hash is `g ^ f1` of `phf_shared::hash(s, &0)`, with `phf_shared` 0.11:
+    /// the value that `get_hash` returns for static and dynamic atoms.
+    /// It is only used when `s` needs a dynamic entry, in which case the string isn't hashed
+    /// again unless it could also be in the static set, which is looked up with its own key.
+    ///
+    /// # Safety
+    ///
+    /// `hash` must be the hash of `s` described above. With another hash, a dynamic entry
+    /// created for `s` would store it, and atoms of `s` would then not have the same hash
+    /// as `Atom::from(s)` in other processes or static sets. This is checked in debug builds.
+    pub unsafe fn from_pre_hashed(s: &str, hash: u32) -> Self {
+        debug_assert!(
+            {
+                let hashes = phf_shared::hash(s, &STORED_HASH_KEY);
+                hashes.g ^ hashes.f1 == hash
+            },
+            "wrong hash for {:?}",
+            s
+        );
+        if s.len() <= MAX_INLINE_LEN {
+            return Self::intern(s);
+        }
+        match lookup_static_set::<Static>().get_index(s) {
+            Some(index) => {
+                #[cfg(feature = "intern_observer")]
+                crate::observer::observe(crate::InternEvent::Static { len: s.len() });
+                Self::pack_static(index)
             }
-        } else {
-            Self::try_static_internal(&*string_to_add).unwrap_or_else(|hash| {
-                let ptr: std::ptr::NonNull<Entry> = dynamic_set().insert(string_to_add, hash.g);
-                let data = ptr.as_ptr() as u64;
-                debug_assert!(0 == data & TAG_MASK);
-                Atom {
-                    // The address of a ptr::NonNull is non-zero
-                    unsafe_data: unsafe { NonZeroU64::new_unchecked(data) },
-                    phantom: PhantomData,
+            None => Self::from_entry(dynamic_set().insert(s, hash)),
+        }
+    }
+
+    /// Take ownership of one reference to a dynamic entry.
+    fn from_entry(ptr: NonNull<Entry>) -> Self {
+        let data = ptr.as_ptr() as u64;
+        debug_assert!(0 == data & TAG_MASK);
+        Atom {
+            // The address of a ptr::NonNull is non-zero
+            unsafe_data: unsafe { NonZeroU64::new_unchecked(data) },
+            phantom: PhantomData,
+        }
+    }
+
+    /// Intern many strings at once.
+    ///
+    /// The returned atoms are identical to calling `Atom::from` on each string in order,
+    /// but strings that need a dynamic entry are grouped by bucket of the dynamic set
+    /// so that each lock is only taken once. This is faster for loading large lists of
+    /// strings, such as a dictionary.
+    pub fn from_iter_dedup<I>(strings: I) -> Vec<Self>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let strings: Vec<I::Item> = strings.into_iter().collect();
+        let mut atoms: Vec<Option<Self>> = Vec::with_capacity(strings.len());
+        let mut dynamic: Vec<(usize, &str, u32)> = Vec::new();
+        for (position, string) in strings.iter().enumerate() {
+            let string = string.as_ref();
+            if string.len() <= MAX_INLINE_LEN {
+                atoms.push(Some(Self::intern(string)));
+            } else {
+                match Self::try_static_internal(string) {
+                    Ok(atom) => {
+                        #[cfg(feature = "intern_observer")]
+                        crate::observer::observe(crate::InternEvent::Static { len: string.len() });
+                        atoms.push(Some(atom))
+                    }
+                    Err(hash) => {
+                        atoms.push(None);
+                        dynamic.push((position, string, hash));
+                    }
                 }
-            })
+            }
+        }
+
+        if dynamic.is_empty() {
+            return atoms.into_iter().map(Option::unwrap).collect();
         }
+        let batch: Vec<(&str, u32)> = dynamic.iter().map(|&(_, s, hash)| (s, hash)).collect();
+        let entries = dynamic_set().insert_batch(&batch);
+        for (ptr, &(position, _, _)) in entries.into_iter().zip(&dynamic) {
+            atoms[position] = Some(Self::from_entry(ptr));
+        }
+        atoms.into_iter().map(Option::unwrap).collect()
+    }
+}
+
+impl<'a, Static: StaticAtomSet> From<Cow<'a, str>> for Atom<Static> {
+    #[inline]
+    fn from(string_to_add: Cow<'a, str>) -> Self {
+        Atom::intern(string_to_add)
     }
 }
 
@@ -239,6 +867,9 @@ impl<Static: StaticAtomSet> Clone for Atom<Static> {
     fn clone(&self) -> Self {
         if self.tag() == DYNAMIC_TAG {
             let entry = self.unsafe_data.get() as *const Entry;
+            // `self` holds a reference, so the count is at least one and the entry
+            // can't be dying. Only a lookup in the set can see a count of zero,
+            // and it never increments one, so a dying entry is never revived.
             unsafe { &*entry }.ref_count.fetch_add(1, SeqCst);
         }
         Atom { ..*self }
@@ -249,15 +880,17 @@ impl<Static> Drop for Atom<Static> {
     #[inline]
     fn drop(&mut self) {
         if self.tag() == DYNAMIC_TAG {
-            let entry = self.unsafe_data.get() as *const Entry;
-            if unsafe { &*entry }.ref_count.fetch_sub(1, SeqCst) == 1 {
-                drop_slow(self)
+            let entry = unsafe { &*(self.unsafe_data.get() as *const Entry) };
+            // Once the count is zero, the entry may be freed by another thread at any time.
+            let placement = entry.placement;
+            if entry.ref_count.fetch_sub(1, SeqCst) == 1 {
+                drop_slow(self, placement)
             }
         }
 
         // Out of line to guide inlining.
-        fn drop_slow<Static>(this: &mut Atom<Static>) {
-            dynamic_set().remove(this.unsafe_data.get() as *mut Entry);
+        fn drop_slow<Static>(this: &mut Atom<Static>, placement: u32) {
+            dynamic_set().remove(this.unsafe_data.get() as *mut Entry, placement);
         }
     }
 }
@@ -266,6 +899,7 @@ impl<Static: StaticAtomSet> ops::Deref for Atom<Static> {
     type Target = str;
 
     #[inline]
+    #[track_caller]
     fn deref(&self) -> &str {
         unsafe {
             match self.tag() {
@@ -279,16 +913,29 @@ impl<Static: StaticAtomSet> ops::Deref for Atom<Static> {
                     let src = inline_atom_slice(&self.unsafe_data);
                     str::from_utf8_unchecked(src.get_unchecked(..(len as usize)))
                 }
-                STATIC_TAG => Static::get().atoms[self.static_index() as usize],
+                STATIC_TAG => {
+                    let atoms = static_set::<Static>().atoms;
+                    match atoms.get(self.raw_static_index() as usize) {
+                        Some(string) => string,
+                        None => static_index_out_of_range(self.raw_static_index(), atoms.len()),
+                    }
+                }
                 _ => debug_unreachable!(),
             }
         }
     }
 }
 
+/// `{:?}` shows how the atom is stored, like `Atom('foo' type=inline)`.
+/// The alternate form `{:#?}` formats it like a string instead, as in `"foo"`,
+/// which is also used for atoms in pretty-printed structures.
 impl<Static: StaticAtomSet> fmt::Debug for Atom<Static> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            return fmt::Debug::fmt(&**self, f);
+        }
+
         let ty_str = unsafe {
             match self.tag() {
                 DYNAMIC_TAG => "dynamic",
@@ -298,17 +945,32 @@ impl<Static: StaticAtomSet> fmt::Debug for Atom<Static> {
             }
         };
 
-        write!(f, "Atom('{}' type={})", &*self, ty_str)
+        write!(f, "Atom('{}' type={})", &**self, ty_str)
+    }
+}
+
+impl<'a, Static: StaticAtomSet> Extend<&'a Atom<Static>> for String {
+    #[inline]
+    fn extend<I: IntoIterator<Item = &'a Atom<Static>>>(&mut self, iter: I) {
+        for atom in iter {
+            self.push_str(atom);
+        }
+    }
+}
+
+impl<'a, Static: StaticAtomSet> ops::Add<&'a Atom<Static>> for &'a Atom<Static> {
+    type Output = Atom<Static>;
+
+    #[inline]
+    fn add(self, other: &'a Atom<Static>) -> Atom<Static> {
+        self.concat(other)
     }
 }
 
 impl<Static: StaticAtomSet> PartialOrd for Atom<Static> {
     #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.unsafe_data == other.unsafe_data {
-            return Some(Equal);
-        }
-        self.as_ref().partial_cmp(other.as_ref())
+        Some(self.cmp(other))
     }
 }
 
@@ -318,21 +980,43 @@ impl<Static: StaticAtomSet> Ord for Atom<Static> {
         if self.unsafe_data == other.unsafe_data {
             return Equal;
         }
-        self.as_ref().cmp(other.as_ref())
+        self.get_str().cmp(other.get_str())
     }
 }
 
 // AsciiExt requires mutating methods, so we just implement the non-mutating ones.
-// We don't need to implement is_ascii because there's no performance improvement
-// over the one from &str.
 impl<Static: StaticAtomSet> Atom<Static> {
-    fn from_mutated_str<F: FnOnce(&mut str)>(s: &str, f: F) -> Self {
+    /// Like [`str::is_ascii`], but checks the bytes of inline atoms without unpacking them.
+    #[inline]
+    pub fn is_ascii(&self) -> bool {
+        if self.tag() == INLINE_TAG {
+            // Every byte but the lowest, which holds the tag and length, is string data,
+            // with zeros after the end of the string.
+            self.unsafe_data.get() & 0x8080_8080_8080_8000 == 0
+        } else {
+            self.get_str().is_ascii()
+        }
+    }
+
+    /// Intern a copy of `s` after normalizing it in place with `f`.
+    ///
+    /// Strings of up to 64 bytes are copied to a buffer on the stack rather than
+    /// into a `String`. Since `f` gets a `&mut str`, it can't change the length of
+    /// the string, only its bytes, as with `make_ascii_lowercase`.
+    ///
+    /// ```
+    /// use string_cache::DefaultAtom;
+    ///
+    /// let atom = DefaultAtom::from_normalized("Color", |s| s.make_ascii_lowercase());
+    /// assert_eq!(atom, "color");
+    /// ```
+    pub fn from_normalized<F: FnOnce(&mut str)>(s: &str, f: F) -> Self {
         let mut buffer = mem::MaybeUninit::<[u8; 64]>::uninit();
         let buffer = unsafe { &mut *buffer.as_mut_ptr() };
 
         if let Some(buffer_prefix) = buffer.get_mut(..s.len()) {
             buffer_prefix.copy_from_slice(s.as_bytes());
-            let as_str = unsafe { ::std::str::from_utf8_unchecked_mut(buffer_prefix) };
+            let as_str = unsafe { str::from_utf8_unchecked_mut(buffer_prefix) };
             f(as_str);
             Atom::from(&*as_str)
         } else {
@@ -348,7 +1032,7 @@ impl<Static: StaticAtomSet> Atom<Static> {
     pub fn to_ascii_uppercase(&self) -> Self {
         for (i, b) in self.bytes().enumerate() {
             if let b'a'..=b'z' = b {
-                return Atom::from_mutated_str(self, |s| s[i..].make_ascii_uppercase());
+                return Atom::from_normalized(self, |s| s[i..].make_ascii_uppercase());
             }
         }
         self.clone()
@@ -360,47 +1044,78 @@ impl<Static: StaticAtomSet> Atom<Static> {
     pub fn to_ascii_lowercase(&self) -> Self {
         for (i, b) in self.bytes().enumerate() {
             if let b'A'..=b'Z' = b {
-                return Atom::from_mutated_str(self, |s| s[i..].make_ascii_lowercase());
+                return Atom::from_normalized(self, |s| s[i..].make_ascii_lowercase());
             }
         }
         self.clone()
     }
 
+    /// Like `to_ascii_uppercase`, but consumes the atom,
+    /// and returns it unchanged if it has no lowercase ASCII letters.
+    pub fn into_ascii_uppercase(self) -> Self {
+        match self.bytes().position(|b| b.is_ascii_lowercase()) {
+            Some(i) => Atom::from_normalized(&self, |s| s[i..].make_ascii_uppercase()),
+            None => self,
+        }
+    }
+
+    /// Like `to_ascii_lowercase`, but consumes the atom,
+    /// and returns it unchanged if it has no uppercase ASCII letters.
+    pub fn into_ascii_lowercase(self) -> Self {
+        match self.bytes().position(|b| b.is_ascii_uppercase()) {
+            Some(i) => Atom::from_normalized(&self, |s| s[i..].make_ascii_lowercase()),
+            None => self,
+        }
+    }
+
     /// Like [`eq_ignore_ascii_case`].
     ///
     /// [`eq_ignore_ascii_case`]: https://doc.rust-lang.org/std/ascii/trait.AsciiExt.html#tymethod.eq_ignore_ascii_case
     pub fn eq_ignore_ascii_case(&self, other: &Self) -> bool {
-        (self == other) || self.eq_str_ignore_ascii_case(&**other)
+        (self == other) || self.eq_str_ignore_ascii_case(other)
     }
 
     /// Like [`eq_ignore_ascii_case`], but takes an unhashed string as `other`.
     ///
     /// [`eq_ignore_ascii_case`]: https://doc.rust-lang.org/std/ascii/trait.AsciiExt.html#tymethod.eq_ignore_ascii_case
     pub fn eq_str_ignore_ascii_case(&self, other: &str) -> bool {
-        (&**self).eq_ignore_ascii_case(other)
+        (**self).eq_ignore_ascii_case(other)
     }
 }
 
+// Out of line, and reporting the location of the caller of `Deref` or `get_hash`,
+// since this is a bug in the code that created the atom.
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn static_index_out_of_range(index: u64, len: usize) -> ! {
+    panic!(
+        "static atom index {} is out of range for a set of {} atoms, \
+         was this atom created for a different StaticAtomSet?",
+        index, len
+    )
+}
+
 #[inline(always)]
 fn inline_atom_slice(x: &NonZeroU64) -> &[u8] {
-        let x: *const NonZeroU64 = x;
-        let mut data = x as *const u8;
-        // All except the lowest byte, which is first in little-endian, last in big-endian.
-        if cfg!(target_endian = "little") {
-            data = unsafe { data.offset(1) };
-        }
-        let len = 7;
-        unsafe { slice::from_raw_parts(data, len) }   
+    let x: *const NonZeroU64 = x;
+    let mut data = x as *const u8;
+    // All except the lowest byte, which is first in little-endian, last in big-endian.
+    if cfg!(target_endian = "little") {
+        data = unsafe { data.offset(1) };
+    }
+    let len = 7;
+    unsafe { slice::from_raw_parts(data, len) }
 }
 
 #[inline(always)]
-fn inline_atom_slice_mut(x: &mut u64) -> &mut [u8] {   
-        let x: *mut u64 = x;
-        let mut data = x as *mut u8;
-        // All except the lowest byte, which is first in little-endian, last in big-endian.
-        if cfg!(target_endian = "little") {
-            data = unsafe { data.offset(1) };
-        }
-        let len = 7;
-        unsafe { slice::from_raw_parts_mut(data, len) }
+fn inline_atom_slice_mut(x: &mut u64) -> &mut [u8] {
+    let x: *mut u64 = x;
+    let mut data = x as *mut u8;
+    // All except the lowest byte, which is first in little-endian, last in big-endian.
+    if cfg!(target_endian = "little") {
+        data = unsafe { data.offset(1) };
+    }
+    let len = 7;
+    unsafe { slice::from_raw_parts_mut(data, len) }
 }
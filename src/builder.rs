@@ -0,0 +1,109 @@
+// Copyright 2014 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::atom::MAX_INLINE_LEN;
+use crate::{Atom, StaticAtomSet};
+use alloc::string::String;
+use core::fmt;
+use core::marker::PhantomData;
+use core::str;
+
+/// Builds an atom from formatted output, like a `String` built with `write!`.
+///
+/// Content that fits in an inline atom stays in a buffer on the stack,
+/// so short results are interned without allocating.
+///
+/// ```
+/// use std::fmt::Write;
+/// use string_cache::{AtomBuilder, DefaultAtom};
+///
+/// let mut builder = AtomBuilder::new();
+/// write!(builder, "{}-{}", "a", 1).unwrap();
+/// let atom: DefaultAtom = builder.finish();
+/// assert_eq!(atom, "a-1");
+/// ```
+pub struct AtomBuilder<Static> {
+    buffer: Buffer,
+    phantom: PhantomData<Static>,
+}
+
+enum Buffer {
+    Inline {
+        bytes: [u8; MAX_INLINE_LEN],
+        len: usize,
+    },
+    Heap(String),
+}
+
+impl<Static: StaticAtomSet> AtomBuilder<Static> {
+    /// Create an empty builder.
+    #[inline]
+    pub fn new() -> Self {
+        AtomBuilder {
+            buffer: Buffer::Inline {
+                bytes: [0; MAX_INLINE_LEN],
+                len: 0,
+            },
+            phantom: PhantomData,
+        }
+    }
+
+    /// The content written so far.
+    pub fn as_str(&self) -> &str {
+        match self.buffer {
+            // Only whole `&str`s are copied in, so the prefix is valid UTF-8.
+            Buffer::Inline { ref bytes, len } => unsafe { str::from_utf8_unchecked(&bytes[..len]) },
+            Buffer::Heap(ref string) => string,
+        }
+    }
+
+    /// Intern the content written so far.
+    pub fn finish(self) -> Atom<Static> {
+        match self.buffer {
+            Buffer::Inline { .. } => Atom::from(self.as_str()),
+            Buffer::Heap(string) => Atom::from(string),
+        }
+    }
+}
+
+impl<Static: StaticAtomSet> Default for AtomBuilder<Static> {
+    #[inline]
+    fn default() -> Self {
+        AtomBuilder::new()
+    }
+}
+
+impl<Static: StaticAtomSet> fmt::Write for AtomBuilder<Static> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.buffer {
+            Buffer::Inline {
+                ref mut bytes,
+                ref mut len,
+            } => {
+                if let Some(dest) = bytes.get_mut(*len..*len + s.len()) {
+                    dest.copy_from_slice(s.as_bytes());
+                    *len += s.len();
+                } else {
+                    let mut string = String::with_capacity(*len + s.len());
+                    string.push_str(unsafe { str::from_utf8_unchecked(&bytes[..*len]) });
+                    string.push_str(s);
+                    self.buffer = Buffer::Heap(string);
+                }
+            }
+            Buffer::Heap(ref mut string) => string.push_str(s),
+        }
+        Ok(())
+    }
+}
+
+impl<Static: StaticAtomSet> fmt::Debug for AtomBuilder<Static> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("AtomBuilder").field(&self.as_str()).finish()
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright 2014 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::{Atom, StaticAtomSet};
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+
+/// An atom that is ordered by [`Atom::cmp_by_identity`] rather than by its string.
+///
+/// This can be used as a `BTreeMap` or `BTreeSet` key when the order of the keys
+/// doesn't matter, avoiding string comparisons.
+pub struct ByIdentity<Static>(pub Atom<Static>);
+
+impl<Static: StaticAtomSet> Clone for ByIdentity<Static> {
+    #[inline]
+    fn clone(&self) -> Self {
+        ByIdentity(self.0.clone())
+    }
+}
+
+impl<Static: StaticAtomSet> fmt::Debug for ByIdentity<Static> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ByIdentity").field(&self.0).finish()
+    }
+}
+
+impl<Static: StaticAtomSet> PartialEq for ByIdentity<Static> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<Static: StaticAtomSet> Eq for ByIdentity<Static> {}
+
+impl<Static: StaticAtomSet> PartialOrd for ByIdentity<Static> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<Static: StaticAtomSet> Ord for ByIdentity<Static> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp_by_identity(&other.0)
+    }
+}
+
+impl<Static: StaticAtomSet> Hash for ByIdentity<Static> {
+    #[inline]
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        self.0.hash(state)
+    }
+}
+
+impl<Static: StaticAtomSet> From<Atom<Static>> for ByIdentity<Static> {
+    #[inline]
+    fn from(atom: Atom<Static>) -> Self {
+        ByIdentity(atom)
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright 2014 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::{Atom, StaticAtomSet};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+
+/// An atom that compares and hashes ASCII-case-insensitively.
+///
+/// This can be used as a `HashMap` key where `"DIV"` and `"div"` are the same key,
+/// for example to match HTML attribute names.
+pub struct AsciiCaseInsensitive<Static>(pub Atom<Static>);
+
+impl<Static: StaticAtomSet> Clone for AsciiCaseInsensitive<Static> {
+    #[inline]
+    fn clone(&self) -> Self {
+        AsciiCaseInsensitive(self.0.clone())
+    }
+}
+
+impl<Static: StaticAtomSet> fmt::Debug for AsciiCaseInsensitive<Static> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("AsciiCaseInsensitive")
+            .field(&self.0)
+            .finish()
+    }
+}
+
+impl<Static: StaticAtomSet> PartialEq for AsciiCaseInsensitive<Static> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq_ignore_ascii_case(&other.0)
+    }
+}
+
+impl<Static: StaticAtomSet> Eq for AsciiCaseInsensitive<Static> {}
+
+impl<Static: StaticAtomSet> Hash for AsciiCaseInsensitive<Static> {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        // The atom's own hash is case-sensitive, so hash the case-folded bytes instead.
+        for byte in self.0.bytes() {
+            state.write_u8(byte.to_ascii_lowercase());
+        }
+        // Like `str`, end with a byte that never appears in UTF-8.
+        state.write_u8(0xff);
+    }
+}
+
+impl<Static: StaticAtomSet> From<Atom<Static>> for AsciiCaseInsensitive<Static> {
+    #[inline]
+    fn from(atom: Atom<Static>) -> Self {
+        AsciiCaseInsensitive(atom)
+    }
+}
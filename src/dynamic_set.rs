@@ -7,24 +7,115 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use parking_lot::Mutex;
-use std::borrow::Cow;
-use std::mem;
-use std::ptr::NonNull;
-use std::sync::atomic::AtomicIsize;
-use std::sync::atomic::Ordering::SeqCst;
-use std::sync::OnceLock;
+// With `no_dynamic`, the set is never created, but the code that uses it still compiles.
+#![cfg_attr(feature = "no_dynamic", allow(dead_code))]
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::mem;
+use core::ops::Deref;
+use core::ptr::NonNull;
+use core::sync::atomic::AtomicIsize;
+use core::sync::atomic::Ordering::SeqCst;
+#[cfg(all(
+    feature = "std",
+    not(all(target_family = "wasm", not(target_feature = "atomics")))
+))]
+use parking_lot::RwLock;
+#[cfg(all(target_family = "wasm", not(target_feature = "atomics")))]
+use single_threaded::RwLock;
+#[cfg(all(
+    not(feature = "std"),
+    not(all(target_family = "wasm", not(target_feature = "atomics")))
+))]
+use spin::RwLock;
 
 const NB_BUCKETS: usize = 1 << 12; // 4096
 const BUCKET_MASK: u32 = (1 << 12) - 1;
 
-pub(crate) struct Set {
-    buckets: Box<[Mutex<Option<Box<Entry>>>]>,
+/// Beyond this many entries, a bucket stores new entries in its overflow map.
+const MAX_CHAIN_LEN: usize = 128;
+
+pub(crate) struct Set<P = KeyedPlacement> {
+    // Looking up an existing entry only takes a read lock,
+    // a write lock is needed to add or remove entries.
+    //
+    // On WebAssembly without threads, the locks are `RefCell`s.
+    //
+    // Neither parking_lot nor spin locks are poisoned by a panic while they are held,
+    // for example if allocating a new entry fails. Buckets stay valid in that case:
+    // an entry is linked into its list only once it is fully allocated.
+    buckets: Box<[RwLock<Bucket>]>,
+    placement: P,
+}
+
+/// Where entries go in the set: the low bits of a placement select the bucket,
+/// and the whole of it keys the overflow maps.
+///
+/// The hash stored in an entry can't be used for this: it must match the hash
+/// of the same string in static sets, so it uses a fixed key and anyone can compute it.
+/// Untrusted strings could then be picked to all have the same hash.
+pub(crate) trait Placement {
+    fn placement(&self, string: &str) -> u32;
+}
+
+/// Places strings by their hash with a key of its own.
+pub(crate) struct KeyedPlacement {
+    key: phf_shared::HashKey,
+}
+
+impl KeyedPlacement {
+    fn random() -> Self {
+        KeyedPlacement { key: random_key() }
+    }
+}
+
+impl Placement for KeyedPlacement {
+    fn placement(&self, string: &str) -> u32 {
+        let hashes = phf_shared::hash(string, &self.key);
+        hashes.g ^ hashes.f1
+    }
+}
+
+/// A key that differs between processes, taken from the random keys of `HashMap`.
+#[cfg(feature = "std")]
+fn random_key() -> phf_shared::HashKey {
+    use std::hash::{BuildHasher, Hasher};
+    std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish()
+}
+
+/// Without `std` there is no source of randomness,
+/// so the key is only as unpredictable as the addresses of the stack and the heap.
+#[cfg(not(feature = "std"))]
+fn random_key() -> phf_shared::HashKey {
+    let on_stack = 0_u8;
+    let on_heap = Box::new(0_u8);
+    let stack = &on_stack as *const u8 as u64;
+    let heap = &*on_heap as *const u8 as u64;
+    stack ^ heap.rotate_left(32)
+}
+
+/// Entries whose placements have the same low bits.
+///
+/// They are kept in a linked list, until it gets long enough that lookups would be slow.
+/// This would normally be very unlikely, but could be caused deliberately by interning
+/// untrusted strings if the placement key became known. Further entries are then kept
+/// in a map keyed by their full placement, with entries of equal placements in a linked list.
+struct Bucket {
+    chain: Option<Box<Entry>>,
+    chain_len: usize,
+    overflow: BTreeMap<u32, Box<Entry>>,
 }
 
 pub(crate) struct Entry {
     pub(crate) string: Box<str>,
     pub(crate) hash: u32,
+    pub(crate) placement: u32,
     pub(crate) ref_count: AtomicIsize,
     next_in_bucket: Option<Box<Entry>>,
 }
@@ -33,80 +124,594 @@ pub(crate) struct Entry {
 // and therefore have have TAG_MASK bits unset, available for tagging.
 pub(crate) const ENTRY_ALIGNMENT: usize = 4;
 
+#[cfg(not(feature = "no_dynamic"))]
 #[test]
 fn entry_alignment_is_sufficient() {
     assert!(mem::align_of::<Entry>() >= ENTRY_ALIGNMENT);
 }
 
+/// Places every string in bucket 42, and spreads them in its overflow map.
+#[cfg(all(test, not(feature = "no_dynamic")))]
+struct OneBucket(KeyedPlacement);
+
+#[cfg(all(test, not(feature = "no_dynamic")))]
+impl Placement for OneBucket {
+    fn placement(&self, string: &str) -> u32 {
+        self.0.placement(string) & !BUCKET_MASK | 42
+    }
+}
+
+#[cfg(not(feature = "no_dynamic"))]
+#[test]
+fn flooded_bucket_overflows() {
+    // A fixed key so the test is reproducible.
+    let set = Set::with_placement(OneBucket(KeyedPlacement { key: 1 }));
+    let placement = |s: &str| set.placement.placement(s);
+    let mut bucket = set.buckets[42].write();
+    // The same stored hash for every string, as if they were picked to collide.
+    let hash = 42;
+    let strings: Vec<String> = (0..1000)
+        .map(|i| format!("flooding string {}", i))
+        .collect();
+    let entries: Vec<_> = strings
+        .iter()
+        .map(|s| bucket.insert(&**s, hash, placement(s)).0)
+        .collect();
+    assert_eq!(bucket.chain_len, MAX_CHAIN_LEN);
+    assert_eq!(bucket.len(), 1000);
+    // So a lookup compares at most the chain and one short overflow list.
+    let longest_list = bucket
+        .overflow
+        .values()
+        .map(|list| iter_list(Some(&**list)).count())
+        .max();
+    assert!(longest_list <= Some(2), "{:?}", longest_list);
+
+    for (s, entry) in strings.iter().zip(&entries) {
+        assert_eq!(bucket.insert(&**s, hash, placement(s)), (*entry, false));
+        assert_eq!(&*unsafe { entry.as_ref() }.string, &**s);
+    }
+    for (s, entry) in strings.iter().zip(entries) {
+        let ref_count = &unsafe { entry.as_ref() }.ref_count;
+        assert_eq!(ref_count.fetch_sub(2, SeqCst), 2);
+        bucket.remove(entry.as_ptr(), placement(s));
+    }
+    assert_eq!(bucket.len(), 0);
+    assert!(bucket.overflow.is_empty());
+}
+
+#[cfg(not(feature = "no_dynamic"))]
+#[test]
+fn equal_hashes_spread_over_buckets() {
+    let set = Set::new();
+    for i in 0..1000 {
+        let string = format!("flooding string {}", i);
+        let placement = set.placement.placement(&string);
+        let bucket = &set.buckets[(placement & BUCKET_MASK) as usize];
+        bucket.write().insert(&*string, 42, placement);
+    }
+    let stats = set.stats();
+    assert_eq!(stats.entries, 1000);
+    assert!(stats.bucket_fill.len() <= 16, "{:?}", stats.bucket_fill);
+}
+
+#[cfg(not(feature = "no_dynamic"))]
+#[test]
+fn remove_unused_entries() {
+    let mut bucket = Bucket::new();
+    let placement = |i: usize| ((i as u32 / 2) << 12) | 7;
+    let strings: Vec<String> = (0..300).map(|i| format!("unused string {}", i)).collect();
+    let entries: Vec<_> = (0..300)
+        .map(|i| bucket.insert(&*strings[i], 7, placement(i)).0)
+        .collect();
+    for entry in entries.iter().step_by(3) {
+        unsafe { entry.as_ref() }.ref_count.store(0, SeqCst);
+    }
+    assert_eq!(bucket.remove_unused(), 100);
+    assert_eq!(bucket.len(), 200);
+    assert_eq!(bucket.remove_unused(), 0);
+
+    // Removing an entry that was already freed does nothing.
+    bucket.remove(entries[0].as_ptr(), placement(0));
+    assert_eq!(bucket.len(), 200);
+    for i in (0..300).filter(|i| i % 3 != 0) {
+        assert_eq!(bucket.find(&strings[i], placement(i)), Some(entries[i]));
+    }
+}
+
+#[cfg(not(feature = "no_dynamic"))]
+#[test]
+fn for_each_entry_visits_buckets_in_order() {
+    let set = Set::new();
+    // Inserted through the buckets, since `Set::insert` may also cache entries per thread.
+    let insert = |string: &str, placement: u32| {
+        let bucket = &set.buckets[(placement & BUCKET_MASK) as usize];
+        bucket.write().insert(string, 0, placement).0
+    };
+    insert("in bucket 9", 9);
+    insert("first in bucket 3", 3);
+    let shared = insert("second in bucket 3", 3 | (1 << 12));
+    unsafe { shared.as_ref() }.ref_count.fetch_add(2, SeqCst);
+
+    let mut entries = Vec::new();
+    set.for_each_entry(|string, ref_count| entries.push((String::from(string), ref_count)));
+    assert_eq!(
+        entries,
+        [
+            (String::from("second in bucket 3"), 3),
+            (String::from("first in bucket 3"), 1),
+            (String::from("in bucket 9"), 1),
+        ]
+    );
+}
+
+/// With the `no_dynamic` feature there is no global set, and any string that needs
+/// a dynamic entry panics, so dynamic atoms never exist.
+#[cfg(feature = "no_dynamic")]
+#[cold]
+pub(crate) fn dynamic_set() -> &'static Set {
+    panic!(
+        "string_cache was built with the `no_dynamic` feature, \
+         so only static and inline atoms can be created"
+    )
+}
+
+#[cfg(not(feature = "no_dynamic"))]
 pub(crate) fn dynamic_set() -> &'static Set {
     // NOTE: Using const initialization for buckets breaks the small-stack test.
     // ```
-    // // buckets: [Mutex<Option<Box<Entry>>>; NB_BUCKETS],
-    // const MUTEX: Mutex<Option<Box<Entry>>> = Mutex::new(None);
-    // let buckets = Box::new([MUTEX; NB_BUCKETS]);
+    // // buckets: [RwLock<Bucket>; NB_BUCKETS],
+    // const LOCK: RwLock<Bucket> = RwLock::new(Bucket::new());
+    // let buckets = Box::new([LOCK; NB_BUCKETS]);
     // ```
-    static DYNAMIC_SET: OnceLock<Set> = OnceLock::new();
+    #[cfg(feature = "std")]
+    {
+        static DYNAMIC_SET: std::sync::OnceLock<Set> = std::sync::OnceLock::new();
+        DYNAMIC_SET.get_or_init(Set::new)
+    }
+    // Without `std`, spin locks are the only portable option.
+    #[cfg(not(feature = "std"))]
+    {
+        static DYNAMIC_SET: spin::Once<Set> = spin::Once::new();
+        DYNAMIC_SET.call_once(Set::new)
+    }
+}
+
+/// A snapshot of the contents of the dynamic set.
+pub(crate) struct Stats {
+    pub(crate) entries: usize,
+    pub(crate) string_bytes: usize,
+    /// `bucket_fill[n]` is the number of buckets with a chain of `n` entries.
+    pub(crate) bucket_fill: Vec<usize>,
+}
+
+/// Statistics about the dynamic set, returned by [`dynamic_set_stats`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DynamicSetStats {
+    /// The number of buckets of the hash table, which is fixed.
+    pub buckets: usize,
+    /// The number of buckets with at least one entry.
+    pub occupied_buckets: usize,
+    /// The number of entries in the fullest bucket.
+    pub longest_chain: usize,
+}
+
+/// Return statistics about how the dynamic atoms currently interned fill the hash table.
+///
+/// There is a single dynamic set for the whole process, shared by atoms of every
+/// `StaticAtomSet`, so this counts entries interned by any code, including other threads.
+/// Buckets are locked one at a time, so with concurrent interning the numbers
+/// may not reflect a single point in time.
+pub fn dynamic_set_stats() -> DynamicSetStats {
+    let stats = stats();
+    DynamicSetStats {
+        buckets: NB_BUCKETS,
+        occupied_buckets: NB_BUCKETS - stats.bucket_fill[0],
+        longest_chain: stats.bucket_fill.len() - 1,
+    }
+}
 
-    DYNAMIC_SET.get_or_init(|| {
-        let buckets = (0..NB_BUCKETS).map(|_| Mutex::new(None)).collect();
-        Set { buckets }
-    })
+/// Return a human-readable summary of the dynamic atoms currently interned.
+///
+/// This includes the number of entries, the total size of their strings,
+/// and how they are distributed among the buckets of the hash table.
+/// Buckets are locked one at a time, so with concurrent interning the numbers
+/// may not reflect a single point in time.
+pub fn memory_report() -> String {
+    use core::fmt::Write;
+
+    let stats = stats();
+    let mut report = format!(
+        "dynamic atoms: {} entries, {} string bytes, {} buckets, longest chain {}\n",
+        stats.entries,
+        stats.string_bytes,
+        NB_BUCKETS,
+        stats.bucket_fill.len() - 1,
+    );
+    report.push_str("bucket fill:");
+    for (chain_length, buckets) in stats.bucket_fill.iter().enumerate() {
+        write!(report, " {}: {}", chain_length, buckets).unwrap();
+    }
+    report
+}
+
+/// Free the dynamic entries that are no longer referenced, returning how many there were.
+///
+/// Entries are normally freed as soon as their last atom is dropped, so this rarely
+/// finds anything. It is meant as a manual maintenance call for memory-sensitive programs,
+/// for example after processing a large document, and locks every bucket in turn.
+pub fn shrink_dynamic_set() -> usize {
+    #[cfg(not(feature = "no_dynamic"))]
+    {
+        dynamic_set().shrink()
+    }
+    #[cfg(feature = "no_dynamic")]
+    {
+        0
+    }
+}
+
+/// Without a dynamic set, report it as empty rather than panicking.
+fn stats() -> Stats {
+    #[cfg(not(feature = "no_dynamic"))]
+    {
+        dynamic_set().stats()
+    }
+    #[cfg(feature = "no_dynamic")]
+    {
+        Stats {
+            entries: 0,
+            string_bytes: 0,
+            bucket_fill: vec![NB_BUCKETS],
+        }
+    }
+}
+
+impl Bucket {
+    const fn new() -> Self {
+        Bucket {
+            chain: None,
+            chain_len: 0,
+            overflow: BTreeMap::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        let mut len = self.chain_len;
+        for list in self.overflow.values() {
+            len += iter_list(Some(&**list)).count()
+        }
+        len
+    }
+
+    /// Return a new reference to a live entry for this string, if there is one.
+    fn find(&self, string: &str, placement: u32) -> Option<NonNull<Entry>> {
+        find_in_list(self.chain.as_deref(), string, placement).or_else(|| {
+            let list = self.overflow.get(&placement).map(|list| &**list);
+            find_in_list(list, string, placement)
+        })
+    }
+
+    /// Return a new reference to an entry for this string, and whether it was just created.
+    ///
+    /// `hash` is only stored in the entry if one is created.
+    fn insert<S>(&mut self, string: S, hash: u32, placement: u32) -> (NonNull<Entry>, bool)
+    where
+        S: Deref<Target = str> + Into<Box<str>>,
+    {
+        if let Some(ptr) = self.find(&string, placement) {
+            return (ptr, false);
+        }
+
+        debug_assert!(mem::align_of::<Entry>() >= ENTRY_ALIGNMENT);
+        let ptr = if self.chain_len < MAX_CHAIN_LEN {
+            self.chain_len += 1;
+            push_front(&mut self.chain, string, hash, placement)
+        } else {
+            let mut list = self.overflow.remove(&placement);
+            let ptr = push_front(&mut list, string, hash, placement);
+            self.overflow.insert(placement, list.unwrap());
+            ptr
+        };
+        (ptr, true)
+    }
+
+    /// Free `ptr` if it is still in this bucket and unused.
+    fn remove(&mut self, ptr: *mut Entry, placement: u32) {
+        if remove_from_list(&mut self.chain, ptr) {
+            self.chain_len -= 1;
+            return;
+        }
+        if let Some(head) = self.overflow.remove(&placement) {
+            let mut list = Some(head);
+            remove_from_list(&mut list, ptr);
+            if let Some(head) = list {
+                self.overflow.insert(placement, head);
+            }
+        }
+    }
+
+    /// Free every entry with a reference count of zero, returning how many there were.
+    fn remove_unused(&mut self) -> usize {
+        let from_chain = remove_unused_from_list(&mut self.chain);
+        self.chain_len -= from_chain;
+        let mut removed = from_chain;
+        for (placement, head) in mem::take(&mut self.overflow) {
+            let mut list = Some(head);
+            removed += remove_unused_from_list(&mut list);
+            if let Some(head) = list {
+                self.overflow.insert(placement, head);
+            }
+        }
+        removed
+    }
+}
+
+fn iter_list(list: Option<&Entry>) -> impl Iterator<Item = &Entry> {
+    core::iter::successors(list, |entry| entry.next_in_bucket.as_deref())
+}
+
+/// Return a new reference to a live entry for this string, if there is one in `list`.
+fn find_in_list(list: Option<&Entry>, string: &str, placement: u32) -> Option<NonNull<Entry>> {
+    for entry in iter_list(list) {
+        if entry.placement == placement && *entry.string == *string {
+            // Only take a reference if the count is above zero. Bumping a count of zero,
+            // even temporarily, would let another reader holding the same read lock
+            // see the entry as live and return it.
+            let live = entry
+                .ref_count
+                .fetch_update(SeqCst, SeqCst, |count| (count > 0).then_some(count + 1))
+                .is_ok();
+            if live {
+                return Some(NonNull::from(entry));
+            }
+            // Uh-oh. The pointer's reference count was zero, which means someone may try
+            // to free it. (Naive attempts to defend against this, for example having the
+            // destructor check to see whether the reference count is indeed zero, don't
+            // work due to ABA.) Thus we need to temporarily add a duplicate string to the
+            // list, unless another live duplicate is found further along.
+        }
+    }
+    None
+}
+
+fn push_front<S>(
+    list: &mut Option<Box<Entry>>,
+    string: S,
+    hash: u32,
+    placement: u32,
+) -> NonNull<Entry>
+where
+    S: Into<Box<str>>,
+{
+    let mut entry = Box::new(Entry {
+        next_in_bucket: list.take(),
+        hash,
+        placement,
+        ref_count: AtomicIsize::new(1),
+        string: string.into(),
+    });
+    let ptr = NonNull::from(&mut *entry);
+    *list = Some(entry);
+    ptr
+}
+
+/// Unlink and free `ptr` if it is an unused entry of `list`, returning whether it was.
+///
+/// `ptr` may have been freed already by `remove_unused`, and its address reused by a live
+/// entry, so it is only dereferenced once found in the list.
+fn remove_from_list(list: &mut Option<Box<Entry>>, ptr: *mut Entry) -> bool {
+    let mut current: &mut Option<Box<Entry>> = list;
+
+    while let Some(entry_ptr) = current.as_mut() {
+        let entry_ptr: *mut Entry = &mut **entry_ptr;
+        if entry_ptr == ptr && unsafe { (*entry_ptr).ref_count.load(SeqCst) } == 0 {
+            mem::drop(mem::replace(current, unsafe {
+                (*entry_ptr).next_in_bucket.take()
+            }));
+            return true;
+        }
+        current = unsafe { &mut (*entry_ptr).next_in_bucket };
+    }
+    false
+}
+
+/// Unlink and free the entries of `list` with a reference count of zero, returning how many.
+fn remove_unused_from_list(list: &mut Option<Box<Entry>>) -> usize {
+    let mut removed = 0;
+    let mut current = list;
+    while let Some(mut entry) = current.take() {
+        if entry.ref_count.load(SeqCst) == 0 {
+            *current = entry.next_in_bucket.take();
+            removed += 1;
+        } else {
+            current = &mut current.insert(entry).next_in_bucket;
+        }
+    }
+    removed
 }
 
 impl Set {
-    pub(crate) fn insert(&self, string: Cow<str>, hash: u32) -> NonNull<Entry> {
-        let bucket_index = (hash & BUCKET_MASK) as usize;
-        let mut linked_list = self.buckets[bucket_index].lock();
-
-        {
-            let mut ptr: Option<&mut Box<Entry>> = linked_list.as_mut();
-
-            while let Some(entry) = ptr.take() {
-                if entry.hash == hash && *entry.string == *string {
-                    if entry.ref_count.fetch_add(1, SeqCst) > 0 {
-                        return NonNull::from(&mut **entry);
-                    }
-                    // Uh-oh. The pointer's reference count was zero, which means someone may try
-                    // to free it. (Naive attempts to defend against this, for example having the
-                    // destructor check to see whether the reference count is indeed zero, don't
-                    // work due to ABA.) Thus we need to temporarily add a duplicate string to the
-                    // list.
-                    entry.ref_count.fetch_sub(1, SeqCst);
-                    break;
-                }
-                ptr = entry.next_in_bucket.as_mut();
+    fn new() -> Self {
+        Set::with_placement(KeyedPlacement::random())
+    }
+}
+
+impl<P: Placement> Set<P> {
+    fn with_placement(placement: P) -> Self {
+        let buckets = (0..NB_BUCKETS)
+            .map(|_| RwLock::new(Bucket::new()))
+            .collect();
+        Set { buckets, placement }
+    }
+
+    pub(crate) fn stats(&self) -> Stats {
+        let mut stats = Stats {
+            entries: 0,
+            string_bytes: 0,
+            bucket_fill: vec![0],
+        };
+        for bucket in self.buckets.iter() {
+            let bucket = bucket.read();
+            let chain_length = bucket.len();
+            let lists = bucket.overflow.values().chain(bucket.chain.as_ref());
+            for entry in lists.flat_map(|list| iter_list(Some(&**list))) {
+                stats.string_bytes += entry.string.len();
+            }
+            stats.entries += chain_length;
+            if stats.bucket_fill.len() <= chain_length {
+                stats.bucket_fill.resize(chain_length + 1, 0);
             }
+            stats.bucket_fill[chain_length] += 1;
         }
-        debug_assert!(mem::align_of::<Entry>() >= ENTRY_ALIGNMENT);
-        let string = string.into_owned();
-        let mut entry = Box::new(Entry {
-            next_in_bucket: linked_list.take(),
-            hash,
-            ref_count: AtomicIsize::new(1),
-            string: string.into_boxed_str(),
+        stats
+    }
+
+    /// Call `f` with the string and reference count of every entry, for white-box tests.
+    ///
+    /// Buckets are visited in index order, and within a bucket the chain comes first,
+    /// then the overflow lists by placement. Only the bucket being visited is locked, so `f`
+    /// must not intern strings, which may need that bucket.
+    #[cfg(test)]
+    pub(crate) fn for_each_entry(&self, mut f: impl FnMut(&str, isize)) {
+        for bucket in self.buckets.iter() {
+            let bucket = bucket.read();
+            let lists = bucket
+                .chain
+                .as_ref()
+                .into_iter()
+                .chain(bucket.overflow.values());
+            for entry in lists.flat_map(|list| iter_list(Some(&**list))) {
+                f(&entry.string, entry.ref_count.load(SeqCst));
+            }
+        }
+    }
+
+    pub(crate) fn insert<S>(&self, string: S, hash: u32) -> NonNull<Entry>
+    where
+        S: Deref<Target = str> + Into<Box<str>>,
+    {
+        #[cfg(feature = "intern_observer")]
+        let len = string.len();
+
+        #[cfg(feature = "thread_local_cache")]
+        if let Some(ptr) = crate::local_cache::lookup(&string, hash) {
+            #[cfg(feature = "intern_observer")]
+            crate::observer::observe(crate::InternEvent::DynamicExisting { len });
+            return ptr;
+        }
+
+        let placement = self.placement.placement(&string);
+        let bucket = &self.buckets[(placement & BUCKET_MASK) as usize];
+        // Most of the time the string is already there,
+        // so first look for it without blocking other readers.
+        let existing = bucket.read().find(&string, placement);
+        let (ptr, _new) = match existing {
+            Some(ptr) => (ptr, false),
+            // Another thread may have added the string since the read lock was released,
+            // `Bucket::insert` looks for it again.
+            None => bucket.write().insert(string, hash, placement),
+        };
+
+        // The observer is called without holding any lock, since it may intern strings.
+        #[cfg(feature = "intern_observer")]
+        crate::observer::observe(if _new {
+            crate::InternEvent::DynamicNew { len }
+        } else {
+            crate::InternEvent::DynamicExisting { len }
         });
-        let ptr = NonNull::from(&mut *entry);
-        *linked_list = Some(entry);
+
+        #[cfg(feature = "thread_local_cache")]
+        crate::local_cache::store(ptr);
+
         ptr
     }
 
-    pub(crate) fn remove(&self, ptr: *mut Entry) {
-        let value: &Entry = unsafe { &*ptr };
-        let bucket_index = (value.hash & BUCKET_MASK) as usize;
+    /// Insert many `(string, hash)` pairs, locking each bucket only once.
+    ///
+    /// The entries are returned in the order of `strings`.
+    pub(crate) fn insert_batch(&self, strings: &[(&str, u32)]) -> Vec<NonNull<Entry>> {
+        let mut placements: Vec<(u32, usize)> = strings
+            .iter()
+            .enumerate()
+            .map(|(position, &(string, _))| (self.placement.placement(string), position))
+            .collect();
+        placements.sort_by_key(|&(placement, _)| placement & BUCKET_MASK);
+        let mut entries = vec![None; strings.len()];
+        #[cfg(feature = "intern_observer")]
+        let mut new_entries = vec![false; strings.len()];
+        let mut rest = &*placements;
+        while let Some(&(first_placement, _)) = rest.first() {
+            let bucket_index = first_placement & BUCKET_MASK;
+            let group_len = rest
+                .iter()
+                .take_while(|&&(placement, _)| placement & BUCKET_MASK == bucket_index)
+                .count();
+            let (group, tail) = rest.split_at(group_len);
+            let mut bucket = self.buckets[bucket_index as usize].write();
+            for &(placement, position) in group {
+                let (string, hash) = strings[position];
+                let (ptr, _new) = bucket.insert(string, hash, placement);
+                entries[position] = Some(ptr);
+                #[cfg(feature = "intern_observer")]
+                {
+                    new_entries[position] = _new;
+                }
+            }
+            rest = tail;
+        }
 
-        let mut linked_list = self.buckets[bucket_index].lock();
-        debug_assert!(value.ref_count.load(SeqCst) == 0);
-        let mut current: &mut Option<Box<Entry>> = &mut linked_list;
+        #[cfg(feature = "intern_observer")]
+        for (&(string, _), new) in strings.iter().zip(new_entries) {
+            let len = string.len();
+            crate::observer::observe(if new {
+                crate::InternEvent::DynamicNew { len }
+            } else {
+                crate::InternEvent::DynamicExisting { len }
+            });
+        }
+        entries.into_iter().map(Option::unwrap).collect()
+    }
 
-        while let Some(entry_ptr) = current.as_mut() {
-            let entry_ptr: *mut Entry = &mut **entry_ptr;
-            if entry_ptr == ptr {
-                mem::drop(mem::replace(current, unsafe {
-                    (*entry_ptr).next_in_bucket.take()
-                }));
-                break;
-            }
-            current = unsafe { &mut (*entry_ptr).next_in_bucket };
+    /// Free an entry whose reference count just dropped to zero.
+    ///
+    /// `placement` must be read before releasing the last reference: `shrink` may free
+    /// the entry before its bucket is locked here.
+    pub(crate) fn remove(&self, ptr: *mut Entry, placement: u32) {
+        let bucket_index = (placement & BUCKET_MASK) as usize;
+        self.buckets[bucket_index].write().remove(ptr, placement);
+    }
+
+    pub(crate) fn shrink(&self) -> usize {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.write().remove_unused())
+            .sum()
+    }
+}
+
+/// WebAssembly without the `atomics` target feature has no threads,
+/// so the dynamic set doesn't need to lock anything.
+#[cfg(all(target_family = "wasm", not(target_feature = "atomics")))]
+mod single_threaded {
+    use core::cell::{Ref, RefCell, RefMut};
+
+    pub(crate) struct RwLock<T>(RefCell<T>);
+
+    // There is only ever one thread.
+    unsafe impl<T: Send> Sync for RwLock<T> {}
+
+    impl<T> RwLock<T> {
+        pub(crate) const fn new(value: T) -> Self {
+            RwLock(RefCell::new(value))
+        }
+
+        pub(crate) fn read(&self) -> Ref<'_, T> {
+            self.0.borrow()
+        }
+
+        pub(crate) fn write(&self) -> RefMut<'_, T> {
+            self.0.borrow_mut()
         }
     }
 }
@@ -0,0 +1,60 @@
+// Copyright 2014 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::hash::{BuildHasherDefault, Hasher};
+
+/// A hasher for maps keyed by atoms.
+///
+/// Atoms already store a hash of their string, see [`Atom::get_hash`], so this hasher
+/// doesn't hash the string again. It only mixes the stored `u32` into the `u64` result
+/// with a rotate, xor and multiply, which is much cheaper. That step is still needed:
+/// `HashMap` selects buckets from the low bits of the result and compares its high bits,
+/// so the 32 bits of the stored hash have to be spread over all 64.
+/// Other keys are hashed with the same mixing, one byte at a time.
+///
+/// [`Atom::get_hash`]: struct.Atom.html#method.get_hash
+#[derive(Default, Clone, Copy)]
+pub struct AtomHasher(u64);
+
+impl Hasher for AtomHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    fn write_u32(&mut self, hash: u32) {
+        // `Atom::hash` writes a single `u32`. Spread it over the whole `u64`,
+        // since hash tables may use either the lowest or the highest bits.
+        self.0 = (self.0.rotate_left(5) ^ u64::from(hash)).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0.rotate_left(5) ^ u64::from(byte)).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        }
+    }
+}
+
+/// A `BuildHasher` for [`AtomHasher`].
+pub type BuildAtomHasher = BuildHasherDefault<AtomHasher>;
+
+/// A concurrent map keyed by atoms, using their precomputed hash.
+#[cfg(feature = "dashmap")]
+pub type AtomDashMap<K, V> = dashmap::DashMap<K, V, BuildAtomHasher>;
+
+/// Create an empty [`AtomDashMap`].
+#[cfg(feature = "dashmap")]
+pub fn new_atom_dash_map<K, V>() -> AtomDashMap<K, V>
+where
+    K: Eq + core::hash::Hash,
+{
+    dashmap::DashMap::with_hasher(BuildAtomHasher::default())
+}
@@ -0,0 +1,47 @@
+// Copyright 2014 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::{Atom, StaticAtomSet};
+use std::io::{self, BufRead};
+
+/// Intern each line of `reader`, for example to load a dictionary with one atom per line.
+///
+/// Like `BufRead::lines`, the trailing `\n` or `\r\n` of each line is removed.
+/// Blank lines give the empty atom rather than being skipped.
+/// A single buffer is reused for all lines, so only lines that need a dynamic entry allocate.
+///
+/// ```
+/// use string_cache::{atoms_from_reader, DefaultAtom};
+///
+/// let atoms: Vec<DefaultAtom> = atoms_from_reader(&b"foo\n\nbar\r\n"[..])
+///     .collect::<std::io::Result<_>>()
+///     .unwrap();
+/// assert_eq!(atoms, ["foo", "", "bar"]);
+/// ```
+pub fn atoms_from_reader<Static, R>(mut reader: R) -> impl Iterator<Item = io::Result<Atom<Static>>>
+where
+    Static: StaticAtomSet,
+    R: BufRead,
+{
+    let mut line = String::new();
+    std::iter::from_fn(move || {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => {
+                let trimmed = match line.strip_suffix('\n') {
+                    Some(line) => line.strip_suffix('\r').unwrap_or(line),
+                    None => &line,
+                };
+                Some(Ok(Atom::from(trimmed)))
+            }
+            Err(error) => Some(Err(error)),
+        }
+    })
+}
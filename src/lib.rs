@@ -16,6 +16,14 @@
 //!
 //! Generated `Atom`s will have assocated macros to intern static strings at compile-time.
 //!
+//! The crate can be used without `std` by disabling the default `std` feature
+//! and enabling the `spin` feature instead, as long as an allocator is available.
+//!
+//! The `no_dynamic` feature removes the global dynamic set, so that only static and inline
+//! atoms can be created and any other string panics. Unlike other features it is not
+//! additive: it applies to every crate in the build, including dependencies that intern
+//! arbitrary strings.
+//!
 //! # Examples
 //!
 //! Here are two examples, one with compile-time `Atom`s, and one without.
@@ -25,10 +33,10 @@
 //! In `Cargo.toml`:
 //! ```toml
 //! [dependencies]
-//! string_cache = "0.8"
+//! string_cache = "0.9"
 //!
 //! [dev-dependencies]
-//! string_cache_codegen = "0.5"
+//! string_cache_codegen = "0.6"
 //! ```
 //!
 //! In `build.rs`:
@@ -76,6 +84,9 @@
 //! # extern crate string_cache;
 //! use string_cache::DefaultAtom;
 //!
+//! # #[cfg(feature = "no_dynamic")]
+//! # fn main() {}
+//! # #[cfg(not(feature = "no_dynamic"))]
 //! # fn main() {
 //! let mut interned_stuff = Vec::new();
 //! let text = "here is a sentence of text that will be tokenised and
@@ -102,7 +113,7 @@
 //!
 
 #![cfg_attr(test, deny(warnings))]
-
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 // Types, such as Atom, that impl Hash must follow the hash invariant: if two objects match
 // with PartialEq, they must also have the same Hash. Clippy warns on types that derive one while
 // manually impl-ing the other, because it seems easy for the two to drift apart, causing the
@@ -114,15 +125,43 @@
 // The `get_hash` function, seen in `atom.rs`, consults that number, plus the global string interner
 // tables. The only way for the resulting hash for two Atoms with the same inner 64-bit number to
 // differ would be if the table entry changed between invocations, and that would be really bad.
-#![allow(clippy::derive_hash_xor_eq)]
+#![allow(clippy::derived_hash_with_manual_eq)]
+
+extern crate alloc;
+
+#[cfg(not(any(feature = "std", feature = "spin")))]
+compile_error!("without the `std` feature, string_cache needs the `spin` feature for its locks");
 
 mod atom;
+mod builder;
+mod by_identity;
+mod case_insensitive;
 mod dynamic_set;
+mod hash;
+#[cfg(feature = "std")]
+mod io;
+#[cfg(feature = "thread_local_cache")]
+mod local_cache;
+#[cfg(feature = "intern_observer")]
+mod observer;
 mod static_sets;
 mod trivial_impls;
 
-pub use atom::Atom;
+pub use atom::{join_atoms, static_index_in, Atom, InteriorNul, NotInternable};
+pub use builder::AtomBuilder;
+pub use by_identity::ByIdentity;
+pub use case_insensitive::AsciiCaseInsensitive;
+pub use dynamic_set::{dynamic_set_stats, memory_report, shrink_dynamic_set, DynamicSetStats};
+#[cfg(feature = "dashmap")]
+pub use hash::{new_atom_dash_map, AtomDashMap};
+pub use hash::{AtomHasher, BuildAtomHasher};
+#[cfg(feature = "std")]
+pub use io::atoms_from_reader;
+#[cfg(feature = "intern_observer")]
+pub use observer::{set_intern_observer, InternEvent};
 pub use static_sets::{EmptyStaticAtomSet, PhfStrSet, StaticAtomSet};
+#[cfg(feature = "serde_support")]
+pub use trivial_impls::deserialize_into;
 
 /// Use this if you don’t care about static atoms.
 pub type DefaultAtom = Atom<EmptyStaticAtomSet>;
@@ -0,0 +1,87 @@
+// Copyright 2014 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A small per-thread cache of recently interned dynamic entries,
+//! enabled by the `thread_local_cache` feature.
+//!
+//! A hit bumps the reference count of the cached entry without locking its bucket.
+//! The cache holds a reference to each of its entries, so they can’t be freed while cached,
+//! and releases it on eviction or when the thread exits.
+
+use crate::dynamic_set::{dynamic_set, Entry};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::ptr::NonNull;
+use core::sync::atomic::Ordering::SeqCst;
+
+const LOCAL_CACHE_SIZE: usize = 16;
+
+struct LocalCache {
+    /// Least recently used first.
+    entries: Vec<NonNull<Entry>>,
+}
+
+thread_local! {
+    static LOCAL_CACHE: RefCell<LocalCache> = const {
+        RefCell::new(LocalCache { entries: Vec::new() })
+    };
+}
+
+impl Drop for LocalCache {
+    fn drop(&mut self) {
+        for ptr in self.entries.drain(..) {
+            release(ptr)
+        }
+    }
+}
+
+fn release(ptr: NonNull<Entry>) {
+    let entry = unsafe { ptr.as_ref() };
+    let placement = entry.placement;
+    if entry.ref_count.fetch_sub(1, SeqCst) == 1 {
+        dynamic_set().remove(ptr.as_ptr(), placement)
+    }
+}
+
+/// Return a new reference to a cached entry for this string, if any.
+pub(crate) fn lookup(string: &str, hash: u32) -> Option<NonNull<Entry>> {
+    LOCAL_CACHE
+        .try_with(|cache| {
+            let mut cache = cache.borrow_mut();
+            let position = cache.entries.iter().rposition(|ptr| {
+                let entry = unsafe { ptr.as_ref() };
+                entry.hash == hash && *entry.string == *string
+            })?;
+            let ptr = cache.entries.remove(position);
+            // The cache’s own reference keeps the count above zero.
+            unsafe { ptr.as_ref() }.ref_count.fetch_add(1, SeqCst);
+            cache.entries.push(ptr);
+            Some(ptr)
+        })
+        .ok()
+        .flatten()
+}
+
+/// Remember an entry that was just returned by the dynamic set.
+pub(crate) fn store(ptr: NonNull<Entry>) {
+    let evicted = LOCAL_CACHE.try_with(|cache| {
+        let mut cache = cache.borrow_mut();
+        unsafe { ptr.as_ref() }.ref_count.fetch_add(1, SeqCst);
+        cache.entries.push(ptr);
+        if cache.entries.len() > LOCAL_CACHE_SIZE {
+            Some(cache.entries.remove(0))
+        } else {
+            None
+        }
+    });
+    // Released outside of the borrow, since freeing the entry locks its bucket.
+    if let Ok(Some(evicted)) = evicted {
+        release(evicted)
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright 2014 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A global hook called whenever a string is interned,
+//! enabled by the `intern_observer` feature.
+
+use alloc::boxed::Box;
+use core::ptr;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::Ordering::SeqCst;
+
+/// How a string was interned, passed to the function given to [`set_intern_observer`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InternEvent {
+    /// The string is in the static set.
+    Static {
+        /// The length in bytes of the string.
+        len: usize,
+    },
+    /// The string is short enough to be stored inline.
+    Inline {
+        /// The length in bytes of the string.
+        len: usize,
+    },
+    /// A new dynamic entry was created for the string.
+    DynamicNew {
+        /// The length in bytes of the string.
+        len: usize,
+    },
+    /// The string already had a dynamic entry.
+    DynamicExisting {
+        /// The length in bytes of the string.
+        len: usize,
+    },
+}
+
+type Observer = Box<dyn Fn(InternEvent) + Send + Sync>;
+
+static OBSERVER: AtomicPtr<Observer> = AtomicPtr::new(ptr::null_mut());
+
+/// Call `observer` every time a string is interned, for example to collect metrics.
+///
+/// The observer is called after any lock of the dynamic set is released,
+/// so it may itself intern strings, which will be observed too.
+/// It replaces any previous observer, which is leaked since other threads
+/// may still be calling it.
+pub fn set_intern_observer<F>(observer: F)
+where
+    F: Fn(InternEvent) + Send + Sync + 'static,
+{
+    let observer: Observer = Box::new(observer);
+    OBSERVER.store(Box::into_raw(Box::new(observer)), SeqCst);
+}
+
+/// Report an event to the observer, if there is one.
+#[inline]
+pub(crate) fn observe(event: InternEvent) {
+    let observer = OBSERVER.load(SeqCst);
+    if !observer.is_null() {
+        // Observers are never freed.
+        unsafe { (*observer)(event) }
+    }
+}
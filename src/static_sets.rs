@@ -20,12 +20,54 @@ pub trait StaticAtomSet: Ord {
     fn get() -> &'static PhfStrSet;
     /// Get the index of the empty string, which is in every set and is used for `Atom::default`.
     fn empty_string_index() -> u32;
+    /// Get the number of atoms that the generated code for this set was built with.
+    ///
+    /// In debug builds, this is checked against the length of `get().atoms`
+    /// whenever a string is looked up in the set, to catch mismatched generated code.
+    /// `None` opts out of the check.
+    fn atom_count() -> Option<u32> {
+        None
+    }
+}
+
+/// Get the static string set, checking that its indices fit in debug builds.
+#[inline]
+#[track_caller]
+pub(crate) fn static_set<Static: StaticAtomSet>() -> &'static PhfStrSet {
+    let set = Static::get();
+    // Static atoms store their index as a `u32`.
+    debug_assert!(
+        set.atoms.len() <= u32::MAX as usize,
+        "static atom sets can have at most u32::MAX atoms"
+    );
+    set
+}
+
+/// Get the static string set to look up a string, also checking it against its expected
+/// size in debug builds.
+///
+/// Only lookups are checked, so that using static atoms stays cheap in debug builds.
+#[inline]
+#[track_caller]
+pub(crate) fn lookup_static_set<Static: StaticAtomSet>() -> &'static PhfStrSet {
+    let set = static_set::<Static>();
+    #[cfg(debug_assertions)]
+    if let Some(count) = Static::atom_count() {
+        assert!(
+            set.atoms.len() == count as usize,
+            "static atom set has {} atoms but its generated code expects {}, \
+             regenerate your atom set",
+            set.atoms.len(),
+            count
+        );
+    }
+    set
 }
 
 /// A string set created using a [perfect hash function], specifically
 /// [Hash, Displace and Compress].
 ///
-/// See the CHD document for the meaning of the struct fields.
+/// See the CHD document for the meaning of the other struct fields.
 ///
 /// [perfect hash function]: https://en.wikipedia.org/wiki/Perfect_hash_function
 /// [Hash, Displace and Compress]: http://cmph.sourceforge.net/papers/esa09.pdf
@@ -38,6 +80,51 @@ pub struct PhfStrSet {
     pub atoms: &'static [&'static str],
     #[doc(hidden)]
     pub hashes: &'static [u32],
+    /// The length in bytes of the longest static atom, or 0 if there is only the empty atom.
+    ///
+    /// Longer strings can't be in the set, so they don't need to be looked up.
+    pub max_len: usize,
+}
+
+impl PhfStrSet {
+    /// Return the index of a string in the set, if it is there.
+    ///
+    /// This is the lookup used to find static atoms, so it is `Some` exactly for
+    /// the strings that `Atom::from` makes static.
+    pub fn get_index(&self, string: &str) -> Option<u32> {
+        self.get_index_or_hash(string).ok()
+    }
+
+    /// Return whether a string is in the set.
+    pub fn contains(&self, string: &str) -> bool {
+        self.get_index(string).is_some()
+    }
+
+    /// Return the stored hash of the atom at an index, or `None` if the index is out of range.
+    ///
+    /// This is what `Atom::get_hash` returns for the static atom with that index.
+    pub fn hash_of_index(&self, i: u32) -> Option<u32> {
+        self.hashes.get(i as usize).copied()
+    }
+
+    /// Return the index of a string in the set, or its hash with the key of
+    /// the set if it had to be hashed to find that it isn't there.
+    pub(crate) fn get_index_or_hash(
+        &self,
+        string: &str,
+    ) -> Result<u32, Option<phf_shared::Hashes>> {
+        // Strings longer than every static atom don't need to be looked up.
+        if string.len() > self.max_len {
+            return Err(None);
+        }
+        let hash = phf_shared::hash(string, &self.key);
+        let index = phf_shared::get_index(&hash, self.disps, self.atoms.len());
+        if self.atoms[index as usize] == string {
+            Ok(index)
+        } else {
+            Err(Some(hash))
+        }
+    }
 }
 
 /// An empty static atom set for when only dynamic strings will be added
@@ -52,8 +139,9 @@ impl StaticAtomSet for EmptyStaticAtomSet {
             key: 0,
             disps: &[(0, 0)],
             atoms: &[""],
-            // "" SipHash'd, and xored with u64_hash_to_u32.
-            hashes: &[0x3ddddef3],
+            // `g ^ f1` of the phf hashes of "" with key 0, like every stored hash.
+            hashes: &[0x40ed2750],
+            max_len: 0,
         };
         &SET
     }
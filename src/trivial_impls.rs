@@ -8,11 +8,18 @@
 // except according to those terms.
 
 use crate::{Atom, StaticAtomSet};
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::fmt;
+use core::str;
 #[cfg(feature = "serde_support")]
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::borrow::Cow;
-use std::fmt;
 
+#[cfg(feature = "std")]
 impl<Static: StaticAtomSet> ::precomputed_hash::PrecomputedHash for Atom<Static> {
     fn precomputed_hash(&self) -> u32 {
         self.get_hash()
@@ -37,13 +44,70 @@ impl<Static: StaticAtomSet> PartialEq<Atom<Static>> for str {
     }
 }
 
+/// Atoms are ordered byte-lexicographically by their string, like `str` itself,
+/// so this is consistent with `Atom`’s own `Ord`.
+impl<Static: StaticAtomSet> PartialOrd<str> for Atom<Static> {
+    fn partial_cmp(&self, other: &str) -> Option<Ordering> {
+        self[..].partial_cmp(other)
+    }
+}
+
+impl<Static: StaticAtomSet> PartialOrd<Atom<Static>> for str {
+    fn partial_cmp(&self, other: &Atom<Static>) -> Option<Ordering> {
+        self.partial_cmp(&other[..])
+    }
+}
+
+impl<Static: StaticAtomSet> PartialEq<&str> for Atom<Static> {
+    fn eq(&self, other: &&str) -> bool {
+        &self[..] == *other
+    }
+}
+
+impl<Static: StaticAtomSet> PartialEq<Atom<Static>> for &str {
+    fn eq(&self, other: &Atom<Static>) -> bool {
+        *self == &other[..]
+    }
+}
+
+impl<Static: StaticAtomSet> PartialEq<char> for Atom<Static> {
+    fn eq(&self, other: &char) -> bool {
+        // Only an atom made of exactly that one char compares equal.
+        self[..] == *other.encode_utf8(&mut [0; 4])
+    }
+}
+
+impl<Static: StaticAtomSet> PartialEq<Atom<Static>> for char {
+    fn eq(&self, other: &Atom<Static>) -> bool {
+        other == self
+    }
+}
+
 impl<Static: StaticAtomSet> PartialEq<String> for Atom<Static> {
     fn eq(&self, other: &String) -> bool {
         self[..] == other[..]
     }
 }
 
-impl<'a, Static: StaticAtomSet> From<&'a str> for Atom<Static> {
+impl<Static: StaticAtomSet> PartialEq<Atom<Static>> for String {
+    fn eq(&self, other: &Atom<Static>) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl<Static: StaticAtomSet> PartialOrd<String> for Atom<Static> {
+    fn partial_cmp(&self, other: &String) -> Option<Ordering> {
+        self[..].partial_cmp(&other[..])
+    }
+}
+
+impl<Static: StaticAtomSet> PartialOrd<Atom<Static>> for String {
+    fn partial_cmp(&self, other: &Atom<Static>) -> Option<Ordering> {
+        self[..].partial_cmp(&other[..])
+    }
+}
+
+impl<Static: StaticAtomSet> From<&str> for Atom<Static> {
     #[inline]
     fn from(string_to_add: &str) -> Self {
         Atom::from(Cow::Borrowed(string_to_add))
@@ -57,6 +121,47 @@ impl<Static: StaticAtomSet> From<String> for Atom<Static> {
     }
 }
 
+impl<Static: StaticAtomSet> From<Box<str>> for Atom<Static> {
+    #[inline]
+    fn from(string_to_add: Box<str>) -> Self {
+        Atom::intern(string_to_add)
+    }
+}
+
+impl<Static: StaticAtomSet> From<Arc<str>> for Atom<Static> {
+    /// The `Arc` is shared so it can’t be moved into the set;
+    /// the string is only copied if a new dynamic entry is needed.
+    #[inline]
+    fn from(string_to_add: Arc<str>) -> Self {
+        Atom::from(&*string_to_add)
+    }
+}
+
+impl<Static: StaticAtomSet> From<char> for Atom<Static> {
+    #[inline]
+    fn from(c: char) -> Self {
+        // A char is at most 4 bytes of UTF-8, so this is always an inline atom.
+        let mut buffer = [0; 4];
+        Atom::from(&*c.encode_utf8(&mut buffer))
+    }
+}
+
+impl<Static: StaticAtomSet> TryFrom<&[u8]> for Atom<Static> {
+    type Error = str::Utf8Error;
+
+    #[inline]
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Atom::from(str::from_utf8(bytes)?))
+    }
+}
+
+impl<Static: StaticAtomSet> From<Atom<Static>> for Box<str> {
+    #[inline]
+    fn from(atom: Atom<Static>) -> Self {
+        atom.into_boxed_str()
+    }
+}
+
 impl<Static: StaticAtomSet> fmt::Display for Atom<Static> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -77,43 +182,307 @@ impl<Static: StaticAtomSet> Serialize for Atom<Static> {
         S: Serializer,
     {
         let string: &str = self.as_ref();
+
+        // Binary formats get the index of static atoms instead of their string,
+        // which is only meaningful with the same static set on both ends.
+        #[cfg(feature = "serde_compact")]
+        if !serializer.is_human_readable() {
+            return if self.is_static() {
+                let index = self.raw_static_index() as u32;
+                serializer.serialize_newtype_variant("Atom", 0, "Static", &index)
+            } else {
+                serializer.serialize_newtype_variant("Atom", 1, "String", string)
+            };
+        }
+
         string.serialize(serializer)
     }
 }
 
+/// Interns borrowed strings directly, so that static and inline atoms never allocate.
+/// Only a `String` handed over by the deserializer can be moved into a dynamic entry.
+#[cfg(feature = "serde_support")]
+struct AtomVisitor<Static: StaticAtomSet>(core::marker::PhantomData<Static>);
+
+#[cfg(feature = "serde_support")]
+impl<'de, Static: StaticAtomSet> serde::de::Visitor<'de> for AtomVisitor<Static> {
+    type Value = Atom<Static>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "an Atom")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(Atom::from(v))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(Atom::from(v))
+    }
+}
+
 #[cfg(feature = "serde_support")]
 impl<'a, Static: StaticAtomSet> Deserialize<'a> for Atom<Static> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'a>,
     {
-        use serde::de;
-        use std::marker::PhantomData;
+        use core::marker::PhantomData;
+
+        #[cfg(feature = "serde_compact")]
+        if !deserializer.is_human_readable() {
+            return deserializer.deserialize_enum(
+                "Atom",
+                compact::VARIANTS,
+                compact::CompactVisitor(PhantomData),
+            );
+        }
+
+        deserializer.deserialize_str(AtomVisitor(PhantomData))
+    }
+}
+
+/// The representation of atoms in binary formats with the `serde_compact` feature:
+/// an enum with the index of static atoms, or the string of other atoms.
+#[cfg(feature = "serde_compact")]
+mod compact {
+    use super::AtomVisitor;
+    use crate::{Atom, StaticAtomSet};
+    use core::fmt;
+    use core::marker::PhantomData;
+    use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, VariantAccess};
+
+    pub(super) const VARIANTS: &[&str] = &["Static", "String"];
+
+    enum Variant {
+        Static,
+        String,
+    }
+
+    impl<'de> Deserialize<'de> for Variant {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct VariantVisitor;
 
-        struct AtomVisitor<Static: StaticAtomSet>(PhantomData<Static>);
+            impl<'de> de::Visitor<'de> for VariantVisitor {
+                type Value = Variant;
 
-        impl<'de, Static: StaticAtomSet> de::Visitor<'de> for AtomVisitor<Static> {
-            type Value = Atom<Static>;
+                fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                    write!(formatter, "`Static` or `String`")
+                }
 
-            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                write!(formatter, "an Atom")
+                fn visit_u64<E>(self, v: u64) -> Result<Variant, E>
+                where
+                    E: de::Error,
+                {
+                    match v {
+                        0 => Ok(Variant::Static),
+                        1 => Ok(Variant::String),
+                        _ => Err(E::invalid_value(de::Unexpected::Unsigned(v), &self)),
+                    }
+                }
+
+                fn visit_str<E>(self, v: &str) -> Result<Variant, E>
+                where
+                    E: de::Error,
+                {
+                    match v {
+                        "Static" => Ok(Variant::Static),
+                        "String" => Ok(Variant::String),
+                        _ => Err(E::unknown_variant(v, VARIANTS)),
+                    }
+                }
             }
 
-            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-            where
-                E: de::Error,
-            {
-                Ok(Atom::from(v))
+            deserializer.deserialize_identifier(VariantVisitor)
+        }
+    }
+
+    /// Deserializes the string of the `String` variant.
+    struct StringSeed<Static: StaticAtomSet>(PhantomData<Static>);
+
+    impl<'de, Static: StaticAtomSet> DeserializeSeed<'de> for StringSeed<Static> {
+        type Value = Atom<Static>;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_str(AtomVisitor(PhantomData))
+        }
+    }
+
+    pub(super) struct CompactVisitor<Static: StaticAtomSet>(pub(super) PhantomData<Static>);
+
+    impl<'de, Static: StaticAtomSet> de::Visitor<'de> for CompactVisitor<Static> {
+        type Value = Atom<Static>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "an Atom")
+        }
+
+        fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+        where
+            A: de::EnumAccess<'de>,
+        {
+            match data.variant()? {
+                (Variant::Static, value) => {
+                    let index: u32 = value.newtype_variant()?;
+                    Atom::from_static_index(index).ok_or_else(|| {
+                        de::Error::invalid_value(
+                            de::Unexpected::Unsigned(index.into()),
+                            &"an index in the static atom set",
+                        )
+                    })
+                }
+                (Variant::String, value) => value.newtype_variant_seed(StringSeed(PhantomData)),
             }
+        }
+    }
+}
 
-            fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
-            where
-                E: de::Error,
-            {
-                Ok(Atom::from(v))
+/// Deserialize a string directly into an atom of the given static set.
+///
+/// This is meant for `#[serde(deserialize_with = "…")]` when the static set
+/// of the field should be spelled out:
+///
+/// ```ignore
+/// #[derive(Deserialize)]
+/// struct Element {
+///     #[serde(deserialize_with = "string_cache::deserialize_into::<FooAtomStaticSet, _>")]
+///     name: FooAtom,
+/// }
+/// ```
+#[cfg(feature = "serde_support")]
+pub fn deserialize_into<'de, Static, D>(deserializer: D) -> Result<Atom<Static>, D::Error>
+where
+    Static: StaticAtomSet,
+    D: Deserializer<'de>,
+{
+    Atom::deserialize(deserializer)
+}
+
+/// Hashes the stored hash of the atom, so that looking up an atom in a `phf::Map`
+/// doesn't hash its string again.
+///
+/// The hash of an inline atom comes from its packed bytes, so a map generated in a build
+/// script is only valid for targets with the same endianness as the build host.
+#[cfg(feature = "phf")]
+impl<Static: StaticAtomSet> phf_shared::PhfHash for Atom<Static> {
+    #[inline]
+    fn phf_hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        state.write_u32(self.get_hash())
+    }
+}
+
+/// Writes the constant expression of a static or inline atom, as the `atom!` macros
+/// generated by `string_cache_codegen` expand to, for use as a key with `phf_codegen`.
+///
+/// Panics for dynamic atoms, which have no constant form. The keys should use the same
+/// static set as the generated map, so that every string of the set is a static atom.
+#[cfg(feature = "phf")]
+impl<Static: StaticAtomSet> phf_shared::FmtConst for Atom<Static> {
+    fn fmt_const(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(index) = self.static_index() {
+            write!(f, "::string_cache::Atom::pack_static({})", index)
+        } else if self.is_inline() {
+            let mut value = 0u64;
+            for (index, byte) in self.bytes().enumerate() {
+                value |= u64::from(byte) << (index * 8 + 8);
             }
+            write!(
+                f,
+                "::string_cache::Atom::pack_inline({}, {})",
+                value,
+                self.len()
+            )
+        } else {
+            panic!("dynamic atom {:?} has no constant form", self.get_str())
         }
+    }
+}
 
-        deserializer.deserialize_str(AtomVisitor(PhantomData))
+#[cfg(feature = "borsh")]
+impl<Static: StaticAtomSet> borsh::BorshSerialize for Atom<Static> {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let string: &str = self.as_ref();
+        borsh::BorshSerialize::serialize(string, writer)
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl<Static: StaticAtomSet> borsh::BorshDeserialize for Atom<Static> {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        String::deserialize_reader(reader).map(Atom::from)
+    }
+}
+
+/// Atoms are archived as their string, like with serde, and interned again on deserialization.
+#[cfg(feature = "rkyv")]
+impl<Static: StaticAtomSet> rkyv::Archive for Atom<Static> {
+    type Archived = rkyv::string::ArchivedString;
+    type Resolver = rkyv::string::StringResolver;
+
+    #[inline]
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        rkyv::string::ArchivedString::resolve_from_str(self, pos, resolver, out)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<Static, S> rkyv::Serialize<S> for Atom<Static>
+where
+    Static: StaticAtomSet,
+    S: rkyv::Fallible + ?Sized,
+    str: rkyv::SerializeUnsized<S>,
+{
+    #[inline]
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        rkyv::string::ArchivedString::serialize_from_str(self, serializer)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<Static, D> rkyv::Deserialize<Atom<Static>, D> for rkyv::string::ArchivedString
+where
+    Static: StaticAtomSet,
+    D: rkyv::Fallible + ?Sized,
+{
+    #[inline]
+    fn deserialize(&self, _: &mut D) -> Result<Atom<Static>, D::Error> {
+        Ok(Atom::from(self.as_str()))
+    }
+}
+
+/// Generates atoms of all three kinds: strings short enough to be inline, atoms of the
+/// static set, and longer strings that need a dynamic entry, in that order of preference
+/// when shrinking.
+#[cfg(feature = "proptest")]
+impl<Static: StaticAtomSet + 'static> proptest::arbitrary::Arbitrary for Atom<Static> {
+    type Parameters = ();
+    type Strategy = proptest::strategy::BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        use proptest::strategy::Strategy;
+
+        let static_atoms = (0..Static::get().atoms.len()).prop_map(|index| {
+            // Short static strings are stored inline,
+            // so go through `Atom::from` rather than `pack_static`.
+            Atom::from(Static::get().atoms[index])
+        });
+        proptest::prop_oneof![
+            "[ -~]{0,7}".prop_map(Atom::from),
+            static_atoms,
+            "\\PC{8,64}".prop_map(Atom::from),
+        ]
+        .boxed()
     }
 }
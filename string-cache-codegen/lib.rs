@@ -19,12 +19,15 @@
 //! build = "build.rs"
 //!
 //! [dependencies]
-//! string_cache = "0.8"
+//! string_cache = "0.9"
 //!
 //! [build-dependencies]
-//! string_cache_codegen = "0.5"
+//! string_cache_codegen = "0.6"
 //! ```
 //!
+//! The generated code needs `string_cache` 0.9: it fills in `PhfStrSet::max_len` and
+//! implements `StaticAtomSet::atom_count`, which earlier versions don't have.
+//!
 //! In `build.rs`:
 //!
 //! ```no_run
@@ -65,10 +68,17 @@
 //! }
 //! ```
 //!
+//! The generated code only refers to `string_cache` and the prelude, so it can be included
+//! in a `no_std` crate, with `string_cache` built with its `spin` feature instead of the
+//! default `std` one. That crate needs version 2 of Cargo's feature resolver, the default since edition 2021,
+//! so that this build dependency doesn't enable `std` for the dependencies it shares with
+//! `string_cache`.
+//!
 
 #![recursion_limit = "128"]
 
 use quote::quote;
+use std::cell::Cell;
 use std::collections::HashSet;
 use std::fs::File;
 use std::io::{self, BufWriter, Write};
@@ -82,6 +92,22 @@ pub struct AtomType {
     macro_name: String,
     macro_doc: Option<String>,
     atoms: HashSet<String>,
+    all_constant: bool,
+    debug_helpers: bool,
+    case_insensitive_macro_name: Option<String>,
+    values: Option<AtomValues>,
+    visibility: String,
+    attributes: Vec<String>,
+    deny_duplicates: bool,
+    unknown_atom_error: bool,
+    duplicates: Vec<String>,
+}
+
+/// Values associated with atoms by `AtomType::with_values`.
+struct AtomValues {
+    value_type: String,
+    function_name: String,
+    entries: Vec<(String, String)>,
 }
 
 impl AtomType {
@@ -101,11 +127,17 @@ impl AtomType {
     /// impl ::string_cache::StaticAtomSet for FooAtomStaticSet {
     ///     // ...
     /// }
+    /// pub const ATOM_FOOATOM__66_6F_6F: FooAtom = /* … */;
     /// #[macro_export]
     /// macro_rules foo_atom {
-    ///    // Expands to: $crate::foo::FooAtom { … }
+    ///    // Expands to: $crate::foo::ATOM_FOOATOM__66_6F_6F { … }
     /// }
+    /// pub const fn foo_atom_static(s: &str) -> Option<FooAtom> { /* … */ }
     /// ```
+    ///
+    /// There is one `pub const` item per atom, named after the code points of its string.
+    /// They are usually referred to through the macro, which can be used wherever a constant
+    /// can, e.g. `const TAG: FooAtom = foo_atom!("foo");` or as a `match` pattern.
     pub fn new(path: &str, macro_name: &str) -> Self {
         assert!(macro_name.ends_with("!"), "`macro_name` must end with '!'");
         AtomType {
@@ -115,6 +147,15 @@ impl AtomType {
             static_set_doc: None,
             macro_doc: None,
             atoms: HashSet::new(),
+            all_constant: false,
+            debug_helpers: false,
+            case_insensitive_macro_name: None,
+            values: None,
+            visibility: "pub".to_owned(),
+            attributes: Vec::new(),
+            deny_duplicates: false,
+            unknown_atom_error: false,
+            duplicates: Vec::new(),
         }
     }
 
@@ -147,9 +188,136 @@ impl AtomType {
         self
     }
 
+    /// Set the visibility of the generated items, `pub` by default.
+    ///
+    /// For example, `.visibility("pub(crate)")` keeps the atom type private to the crate.
+    /// The macros are always exported.
+    pub fn visibility(&mut self, visibility: &str) -> &mut Self {
+        self.visibility = visibility.to_owned();
+        self
+    }
+
+    /// Add attributes to the generated static set struct.
+    ///
+    /// For example, `.attributes(&["#[derive(Debug)]"])`.
+    /// The struct already derives `PartialEq`, `Eq`, `PartialOrd` and `Ord`.
+    pub fn attributes<I>(&mut self, attributes: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.attributes
+            .extend(attributes.into_iter().map(|a| a.as_ref().to_owned()));
+        self
+    }
+
+    /// Also generate a slice of all atoms in the set.
+    ///
+    /// For `AtomType::new("foo::FooAtom", "foo_atom!")`, this is
+    /// `pub static FOO_ATOM_ALL: &[FooAtom]`, including the empty atom.
+    /// Atoms stored as part of the static set come first, in the order of their index,
+    /// followed by atoms short enough to be stored inline.
+    pub fn with_all_constant(&mut self, all_constant: bool) -> &mut Self {
+        self.all_constant = all_constant;
+        self
+    }
+
+    /// Also derive `Debug` for the static set struct, and give it a method returning
+    /// the strings of its static table, to inspect the generated set at runtime.
+    ///
+    /// For `AtomType::new("foo::FooAtom", "foo_atom!")`, this is
+    /// `FooAtomStaticSet::atoms() -> &'static [&'static str]`, in the order of their index.
+    /// Atoms short enough to be stored inline are not in the table, except the empty atom.
+    pub fn with_debug_helpers(&mut self, debug_helpers: bool) -> &mut Self {
+        self.debug_helpers = debug_helpers;
+        self
+    }
+
+    /// Also generate a macro comparing an atom to a static atom ASCII-case-insensitively.
+    ///
+    /// `macro_name` must end with `!`. For example with `foo_atom_ci!`,
+    /// `foo_atom_ci!("div", &atom)` is `true` for atoms of `"div"`, `"DIV"`, `"Div"`, etc.
+    /// It is an expression rather than a pattern, so in a `match` it is used as a guard:
+    ///
+    /// ```ignore
+    /// match name {
+    ///     ref name if foo_atom_ci!("div", name) => {}
+    ///     _ => {}
+    /// }
+    /// ```
+    ///
+    /// Only atoms that are already ASCII lowercase are accepted by this macro, so that
+    /// atoms differing only by case, like `"viewBox"` and `"viewbox"`, can't be confused.
+    pub fn with_case_insensitive_macro(&mut self, macro_name: &str) -> &mut Self {
+        assert!(macro_name.ends_with("!"), "`macro_name` must end with '!'");
+        self.case_insensitive_macro_name =
+            Some(macro_name[..macro_name.len() - "!".len()].to_owned());
+        self
+    }
+
+    /// Associate a value with some atoms, and generate a function to look them up.
+    ///
+    /// `value_type` and each value are Rust code, resolved in the module where the
+    /// generated code is included. The atoms are added to the builder if they weren't already.
+    /// For example, `.with_values("u32", "foo_atom_flags", vec![("foo", "1"), ("bar", "2")])`
+    /// generates:
+    ///
+    /// ```ignore
+    /// pub fn foo_atom_flags(atom: &FooAtom) -> Option<&'static u32> {
+    ///     // ...
+    /// }
+    /// ```
+    ///
+    /// The function matches on the atom's representation, so the string is not hashed or
+    /// compared again.
+    pub fn with_values<I, A, V>(
+        &mut self,
+        value_type: &str,
+        function_name: &str,
+        values: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = (A, V)>,
+        A: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let entries: Vec<(String, String)> = values
+            .into_iter()
+            .map(|(atom, value)| (atom.as_ref().to_owned(), value.as_ref().to_owned()))
+            .collect();
+        self.atoms
+            .extend(entries.iter().map(|(atom, _)| atom.clone()));
+        self.values = Some(AtomValues {
+            value_type: value_type.to_owned(),
+            function_name: function_name.to_owned(),
+            entries,
+        });
+        self
+    }
+
+    /// Make `write_to` fail if an atom was added more than once.
+    ///
+    /// By default atoms added more than once by `atom` or `atoms` are silently deduplicated.
+    /// Merging with `extend_from` or adding atoms in `with_values` never counts as a duplicate.
+    pub fn deny_duplicates(&mut self, deny_duplicates: bool) -> &mut Self {
+        self.deny_duplicates = deny_duplicates;
+        self
+    }
+
+    /// Make the macro give a clear error for string literals that are not in the set.
+    ///
+    /// Without this, a typo like `foo_atom!("dvi")` fails with "no rules expected the token",
+    /// with this it fails with `"dvi" is not a static atom in set FooAtom`.
+    pub fn with_unknown_atom_error(&mut self, unknown_atom_error: bool) -> &mut Self {
+        self.unknown_atom_error = unknown_atom_error;
+        self
+    }
+
     /// Adds an atom to the builder
     pub fn atom(&mut self, s: &str) -> &mut Self {
-        self.atoms.insert(s.to_owned());
+        if !self.atoms.insert(s.to_owned()) {
+            self.duplicates.push(s.to_owned());
+        }
         self
     }
 
@@ -159,16 +327,61 @@ impl AtomType {
         I: IntoIterator,
         I::Item: AsRef<str>,
     {
-        self.atoms
-            .extend(iter.into_iter().map(|s| s.as_ref().to_owned()));
+        for s in iter {
+            self.atom(s.as_ref());
+        }
+        self
+    }
+
+    /// Adds the atoms listed in a file, one per line.
+    ///
+    /// Lines are trimmed, and blank lines and lines starting with `#` are skipped.
+    /// A build script using this should also print `cargo:rerun-if-changed=` with the path.
+    pub fn atoms_from_file(&mut self, path: &Path) -> io::Result<&mut Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let atoms = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        Ok(self.atoms(atoms))
+    }
+
+    /// Adds all the atoms of another builder to this one
+    ///
+    /// This can be used to assemble a single set from atom lists contributed
+    /// by several modules. Atoms present in both builders are only added once.
+    pub fn extend_from(&mut self, other: &AtomType) -> &mut Self {
+        self.atoms.extend(other.atoms.iter().cloned());
         self
     }
 
     /// Write generated code to `destination`.
+    ///
+    /// With `deny_duplicates(true)`, this returns an `InvalidInput` error naming the atoms
+    /// that were added more than once, and nothing is written.
     pub fn write_to<W>(&mut self, mut destination: W) -> io::Result<()>
     where
         W: Write,
     {
+        // Static atoms are packed with a `u32` index. Strings of up to 7 bytes are stored
+        // inline instead, and the empty string is always added to the static set.
+        let static_len = self.atoms.iter().filter(|s| s.len() > 7).count() + 1;
+        if static_len > u32::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} static atoms is too many, at most {} are supported",
+                    static_len,
+                    u32::MAX
+                ),
+            ));
+        }
+        if self.deny_duplicates && !self.duplicates.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("duplicate atoms: {:?}", self.duplicates),
+            ));
+        }
         destination.write_all(
             self.to_tokens()
                 .to_string()
@@ -181,33 +394,70 @@ impl AtomType {
         )
     }
 
+    /// Return the generated code as a string, instead of writing it somewhere.
+    ///
+    /// This is the same code as `write_to` writes, which can be useful for post-processing
+    /// it or bundling several generated modules.
+    pub fn write_to_string(&mut self) -> io::Result<String> {
+        let mut buffer = Vec::new();
+        self.write_to(&mut buffer)?;
+        Ok(String::from_utf8(buffer).expect("generated code is UTF-8"))
+    }
+
+    #[allow(clippy::wrong_self_convention)]
     fn to_tokens(&mut self) -> proc_macro2::TokenStream {
         // `impl Default for Atom` requires the empty string to be in the static set.
         // This also makes sure the set in non-empty,
         // which would cause divisions by zero in rust-phf.
         self.atoms.insert(String::new());
 
+        // Sort the atoms so that the output does not depend on `HashSet` iteration order,
+        // which could change with the toolchain or platform.
+        let mut sorted_atoms: Vec<&str> = self.atoms.iter().map(String::as_str).collect();
+        sorted_atoms.sort_unstable();
+
         // Strings over 7 bytes + empty string added to static set.
         // Otherwise stored inline.
-        let (static_strs, inline_strs): (Vec<_>, Vec<_>) = self
-            .atoms
-            .iter()
-            .map(String::as_str)
+        let (static_strs, inline_strs): (Vec<_>, Vec<_>) = sorted_atoms
+            .into_iter()
             .partition(|s| s.len() > 7 || s.is_empty());
 
         // Static strings
-        let hash_state = phf_generator::generate_hash(&static_strs);
+        // Try key 0 first, the key of the hashes stored for atoms. If it works, interning
+        // a string that isn't in the set only hashes it once, since the phf lookup already
+        // gives the hash of its dynamic entry. Otherwise the string is hashed again with
+        // key 0.
+        //
+        // `phf_generator` picks the keys to try, so the first attempt, which is the first
+        // `static_strs.len()` calls to the hash function, uses key 0 instead of its key.
+        // Which key the result is for is then checked rather than inferred from the calls,
+        // so that a change in how `phf_generator` calls the hash function can't silently
+        // give a wrong key.
+        let calls = Cell::new(0);
+        let hash_state = phf_generator::generate_hash_with_hash_fn(&static_strs, |s, &key| {
+            let first_attempt = calls.get() < static_strs.len();
+            calls.set(calls.get() + 1);
+            phf_shared::hash(*s, &if first_attempt { 0 } else { key })
+        });
         let phf_generator::HashState { key, disps, map } = hash_state;
+        let key = [0, key]
+            .iter()
+            .copied()
+            .find(|key| is_perfect_hash(&static_strs, *key, &disps, &map))
+            .expect("phf_generator returned a hash state that matches neither key");
         let (disps0, disps1): (Vec<_>, Vec<_>) = disps.into_iter().unzip();
         let atoms: Vec<&str> = map.iter().map(|&idx| static_strs[idx]).collect();
         let empty_string_index = atoms.iter().position(|s| s.is_empty()).unwrap() as u32;
         let indices = 0..atoms.len() as u32;
 
+        let max_len = atoms.iter().map(|s| s.len()).max().unwrap_or(0);
         let hashes: Vec<u32> = atoms
             .iter()
             .map(|string| {
-                let hash = phf_shared::hash(string, &key);
-                (hash.g ^ hash.f1) as u32
+                // The same fixed key as dynamic atoms, rather than the key of this set,
+                // so that equal strings have equal hashes in every set.
+                let hash = phf_shared::hash(string, &0);
+                hash.g ^ hash.f1
             })
             .collect();
 
@@ -215,7 +465,7 @@ impl AtomType {
         let type_name = path_parts.next().unwrap();
         let module = match path_parts.next() {
             Some(m) => format!("$crate::{}", m),
-            None => format!("$crate"),
+            None => "$crate".to_owned(),
         };
         let atom_doc = match self.atom_doc {
             Some(ref doc) => quote!(#[doc = #doc]),
@@ -229,11 +479,24 @@ impl AtomType {
             Some(ref doc) => quote!(#[doc = #doc]),
             None => quote!(),
         };
+        let parse = |code: &str| {
+            code.parse::<proc_macro2::TokenStream>()
+                .unwrap_or_else(|e| panic!("invalid Rust code {:?}: {}", code, e))
+        };
+        let visibility = parse(&self.visibility);
+        let attributes: Vec<_> = self.attributes.iter().map(|a| parse(a)).collect();
         let new_term =
             |string: &str| proc_macro2::Ident::new(string, proc_macro2::Span::call_site());
         let static_set_name = new_term(&format!("{}StaticSet", type_name));
+        let atom_count_name = new_term(&format!("{}_COUNT", screaming_snake_case(type_name)));
+        let all_name = new_term(&format!("{}_ALL", screaming_snake_case(type_name)));
+        let lookup_fn_name = new_term(&format!(
+            "{}_static",
+            screaming_snake_case(type_name).to_lowercase()
+        ));
+        let atom_count = atoms.len() as u32;
         let type_name = new_term(type_name);
-        let macro_name = new_term(&*self.macro_name);
+        let macro_name = new_term(&self.macro_name);
         let module = module.parse::<proc_macro2::TokenStream>().unwrap();
         let atom_prefix = format!("ATOM_{}_", type_name.to_string().to_uppercase());
         let new_const_name = |atom: &str| {
@@ -253,7 +516,7 @@ impl AtomType {
 
                 let mut value = 0u64;
                 for (index, c) in s.bytes().enumerate() {
-                    value = value | ((c as u64) << (index * 8 + 8));
+                    value |= (c as u64) << (index * 8 + 8);
                 }
 
                 let len = s.len() as u8;
@@ -264,13 +527,113 @@ impl AtomType {
         let (inline_values, inline_lengths): (Vec<_>, Vec<_>) =
             inline_values_and_lengths.into_iter().unzip();
 
+        let all_constant = if self.all_constant {
+            quote! {
+                /// All the atoms of the set, static ones first and then inline ones.
+                #visibility static #all_name: &[#type_name] = &[
+                    #(#const_names,)*
+                    #(#inline_const_names,)*
+                ];
+            }
+        } else {
+            quote!()
+        };
+
+        let (debug_derive, debug_methods) = if self.debug_helpers {
+            (
+                quote!(#[derive(Debug)]),
+                quote! {
+                    impl #static_set_name {
+                        /// The strings of the static table, in the order of their index.
+                        #visibility fn atoms() -> &'static [&'static str] {
+                            <Self as ::string_cache::StaticAtomSet>::get().atoms
+                        }
+                    }
+                },
+            )
+        } else {
+            (quote!(), quote!())
+        };
+
+        let unknown_atom_arm = if self.unknown_atom_error {
+            let message = format!("\" is not a static atom in set {}", type_name);
+            quote! {
+                ($s:literal) => {
+                    compile_error!(concat!("\"", $s, #message))
+                };
+            }
+        } else {
+            quote!()
+        };
+
+        let byte_str = |s: &&str| proc_macro2::Literal::byte_string(s.as_bytes());
+        let byte_strs: Vec<_> = atoms.iter().map(byte_str).collect();
+        let inline_byte_strs: Vec<_> = inline_strs.iter().map(byte_str).collect();
+
+        let case_insensitive_macro = match self.case_insensitive_macro_name {
+            Some(ref macro_name) => {
+                let macro_name = new_term(macro_name);
+                let (lowercase_strs, lowercase_const_names): (Vec<&str>, Vec<_>) = atoms
+                    .iter()
+                    .zip(&const_names)
+                    .chain(inline_strs.iter().zip(&inline_const_names))
+                    .filter(|(s, _)| !s.bytes().any(|b| b.is_ascii_uppercase()))
+                    .map(|(s, const_name)| (*s, const_name))
+                    .unzip();
+                quote! {
+                    #[macro_export]
+                    macro_rules! #macro_name {
+                        #(
+                            (#lowercase_strs, $atom:expr) => {
+                                ::string_cache::Atom::eq_ignore_ascii_case(
+                                    &#module::#lowercase_const_names,
+                                    $atom,
+                                )
+                            };
+                        )*
+                    }
+                }
+            }
+            None => quote!(),
+        };
+
+        let values_function = match self.values {
+            Some(ref values) => {
+                let value_type = parse(&values.value_type);
+                let function_name = new_term(&values.function_name);
+                let value_count = values.entries.len();
+                let value_atoms = values.entries.iter().map(|(atom, _)| new_const_name(atom));
+                let value_exprs = values.entries.iter().map(|(_, value)| parse(value));
+                let value_indices = 0..value_count;
+                quote! {
+                    /// Return the value associated with this atom, if any.
+                    #visibility fn #function_name(atom: &#type_name) -> Option<&'static #value_type> {
+                        // `value_type` is also used in the return type, where it may need `'static`.
+                        #[allow(clippy::redundant_static_lifetimes)]
+                        static VALUES: [#value_type; #value_count] = [#(#value_exprs),*];
+                        match *atom {
+                            #(
+                                #value_atoms => Some(&VALUES[#value_indices]),
+                            )*
+                            _ => None,
+                        }
+                    }
+                }
+            }
+            None => quote!(),
+        };
+
         quote! {
             #atom_doc
-            pub type #type_name = ::string_cache::Atom<#static_set_name>;
+            #visibility type #type_name = ::string_cache::Atom<#static_set_name>;
 
             #static_set_doc
+            #(#attributes)*
+            #debug_derive
             #[derive(PartialEq, Eq, PartialOrd, Ord)]
-            pub struct #static_set_name;
+            #visibility struct #static_set_name;
+
+            #debug_methods
 
             impl ::string_cache::StaticAtomSet for #static_set_name {
                 fn get() -> &'static ::string_cache::PhfStrSet {
@@ -278,22 +641,31 @@ impl AtomType {
                         key: #key,
                         disps: &[#((#disps0, #disps1)),*],
                         atoms: &[#(#atoms),*],
-                        hashes: &[#(#hashes),*]
+                        hashes: &[#(#hashes),*],
+                        max_len: #max_len,
                     };
                     &SET
                 }
                 fn empty_string_index() -> u32 {
                     #empty_string_index
                 }
+                fn atom_count() -> Option<u32> {
+                    Some(#atom_count_name)
+                }
             }
 
+            /// The number of atoms in the static set.
+            #visibility const #atom_count_name: u32 = #atom_count;
+
             #(
-                pub const #const_names: #type_name = #type_name::pack_static(#indices);
+                #visibility const #const_names: #type_name = #type_name::pack_static(#indices);
             )*
             #(
-                pub const #inline_const_names: #type_name = #type_name::pack_inline(#inline_values, #inline_lengths);
+                #visibility const #inline_const_names: #type_name = #type_name::pack_inline(#inline_values, #inline_lengths);
             )*
 
+            #all_constant
+
             #macro_doc
             #[macro_export]
             macro_rules! #macro_name {
@@ -303,7 +675,28 @@ impl AtomType {
                 #(
                     (#inline_strs) => { #module::#inline_const_names };
                 )*
+                #unknown_atom_arm
             }
+
+            /// Return the atom for a string of the set, or `None` for other strings.
+            ///
+            /// Unlike the macro, this works with strings that are not literals,
+            /// including in `const` contexts.
+            #visibility const fn #lookup_fn_name(s: &str) -> Option<#type_name> {
+                match s.as_bytes() {
+                    #(
+                        #byte_strs => Some(#const_names),
+                    )*
+                    #(
+                        #inline_byte_strs => Some(#inline_const_names),
+                    )*
+                    _ => None,
+                }
+            }
+
+            #case_insensitive_macro
+
+            #values_function
         }
     }
 
@@ -315,3 +708,25 @@ impl AtomType {
         self.write_to(BufWriter::new(File::create(path)?))
     }
 }
+
+/// `FooAtom` -> `FOO_ATOM`
+fn screaming_snake_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut previous_is_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && previous_is_lower {
+            result.push('_');
+        }
+        previous_is_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        result.push(c.to_ascii_uppercase());
+    }
+    result
+}
+
+/// Whether `key` and `disps` map each of `strs` to its own slot in `map`.
+fn is_perfect_hash(strs: &[&str], key: u64, disps: &[(u32, u32)], map: &[usize]) -> bool {
+    strs.iter().enumerate().all(|(i, s)| {
+        let hashes = phf_shared::hash(*s, &key);
+        map[phf_shared::get_index(&hashes, disps, map.len()) as usize] == i
+    })
+}
@@ -6,6 +6,8 @@
 // This is a separate test program rather than a `#[test] fn` among others
 // to make sure that nothing else has already initialized the map in this process.
 fn main() {
+    // There is no map to initialize with `no_dynamic`, and the atom would panic.
+    #[cfg(not(feature = "no_dynamic"))]
     std::thread::Builder::new()
         .stack_size(50_000)
         .spawn(|| {